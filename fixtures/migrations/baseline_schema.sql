@@ -0,0 +1,145 @@
+-- The schema create_database() set up before migrations existed, kept to
+-- test that such databases (user_version 0) are adopted and migrated.
+
+CREATE TABLE IF NOT EXISTS food_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    weight_grams REAL NOT NULL,
+    calcium_ca REAL NOT NULL,
+    carbohydrates REAL NOT NULL,
+    cholesterol REAL NOT NULL,
+    energy REAL NOT NULL,
+    fatty_acids_saturated REAL NOT NULL,
+    total_lipid_fat REAL NOT NULL,
+    fatty_acids_trans REAL NOT NULL,
+    iron_fe REAL NOT NULL,
+    fiber_dietary REAL NOT NULL,
+    potassium_k REAL NOT NULL,
+    sodium_na REAL NOT NULL,
+    protein REAL NOT NULL,
+    sugars_total REAL NOT NULL,
+    sugars_added REAL NOT NULL,
+    vitamin_d REAL NOT NULL,
+    alanine REAL NOT NULL,
+    alcohol_ethyl REAL NOT NULL,
+    arginine REAL NOT NULL,
+    ash REAL NOT NULL,
+    aspartic_acid REAL NOT NULL,
+    betaine REAL NOT NULL,
+    caffeine REAL NOT NULL,
+    campesterol REAL NOT NULL,
+    carotene_alpha REAL NOT NULL,
+    carotene_beta REAL NOT NULL,
+    vitamin_d3 REAL NOT NULL,
+    choline_total REAL NOT NULL,
+    cryptoxanthin_beta REAL NOT NULL,
+    copper_cu REAL NOT NULL,
+    cystine REAL NOT NULL,
+    energy_kj REAL NOT NULL,
+    vitamin_d2 REAL NOT NULL,
+    fatty_acids_monounsaturated REAL NOT NULL,
+    fatty_acids_polyunsaturated REAL NOT NULL,
+    fatty_acids_transmonoenoic REAL NOT NULL,
+    fatty_acids_transpolyenoic REAL NOT NULL,
+    fluoride_f REAL NOT NULL,
+    folate_total REAL NOT NULL,
+    folic_acid REAL NOT NULL,
+    folate_dfe REAL NOT NULL,
+    folate_food REAL NOT NULL,
+    fructose REAL NOT NULL,
+    galactose REAL NOT NULL,
+    glutamic_acid REAL NOT NULL,
+    glucose_dextrose REAL NOT NULL,
+    glycine REAL NOT NULL,
+    histidine REAL NOT NULL,
+    hydroxyproline REAL NOT NULL,
+    isoleucine REAL NOT NULL,
+    lactose REAL NOT NULL,
+    leucine REAL NOT NULL,
+    lutein_zeaxanthin REAL NOT NULL,
+    lycopene REAL NOT NULL,
+    lysine REAL NOT NULL,
+    maltose REAL NOT NULL,
+    methionine REAL NOT NULL,
+    magnesium_mg REAL NOT NULL,
+    menaquinone REAL NOT NULL,
+    manganese_mn REAL NOT NULL,
+    niacin REAL NOT NULL,
+    vitamin_e_added REAL NOT NULL,
+    vitamin_b_added REAL NOT NULL,
+    adjusted_protein REAL NOT NULL,
+    phosphorus_p REAL NOT NULL,
+    pantothenic_acid REAL NOT NULL,
+    phenylalanine REAL NOT NULL,
+    phytosterols REAL NOT NULL,
+    proline REAL NOT NULL,
+    retinol REAL NOT NULL,
+    riboflavin REAL NOT NULL,
+    selenium_se REAL NOT NULL,
+    serine REAL NOT NULL,
+    betasitosterol REAL NOT NULL,
+    starch REAL NOT NULL,
+    stigmasterol REAL NOT NULL,
+    sucrose REAL NOT NULL,
+    theobromine REAL NOT NULL,
+    thiamin REAL NOT NULL,
+    threonine REAL NOT NULL,
+    vitamin_e_alphatocopherol REAL NOT NULL,
+    tocopherol_beta REAL NOT NULL,
+    tocopherol_delta REAL NOT NULL,
+    tocopherol_gamma REAL NOT NULL,
+    tryptophan REAL NOT NULL,
+    tyrosine REAL NOT NULL,
+    valine REAL NOT NULL,
+    vitamin_a_iu REAL NOT NULL,
+    vitamin_a_rae REAL NOT NULL,
+    vitamin_b12 REAL NOT NULL,
+    vitamin_b6 REAL NOT NULL,
+    vitamin_c_total_ascorbic_acid REAL NOT NULL,
+    vitamin_k_phylloquinone REAL NOT NULL,
+    dihydrophylloquinone REAL NOT NULL,
+    water REAL NOT NULL,
+    zinc_zn REAL NOT NULL,
+    tocotrienol_alpha REAL NOT NULL,
+    tocotrienol_beta REAL NOT NULL,
+    tocotrienol_gamma REAL NOT NULL,
+    tocotrienol_delta REAL NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS pantry (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    food_id INTEGER NOT NULL,
+    weight_grams REAL NOT NULL,
+    weight_grams_remaining REAL NOT NULL,
+    FOREIGN KEY (food_id) REFERENCES food_items (id)
+);
+
+CREATE TABLE IF NOT EXISTS recipes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS recipe_ingredients (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    recipe_id INTEGER NOT NULL,
+    food_id INTEGER NOT NULL,
+    weight_grams REAL NOT NULL,
+    FOREIGN KEY (recipe_id) REFERENCES recipes (id),
+    FOREIGN KEY (food_id) REFERENCES food_items (id),
+    UNIQUE (recipe_id, food_id)
+);
+
+CREATE TABLE IF NOT EXISTS entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS entry_foods (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entry_id INTEGER NOT NULL,
+    food_id INTEGER NOT NULL,
+    weight_grams REAL NOT NULL,
+    FOREIGN KEY (entry_id) REFERENCES entries (id),
+    FOREIGN KEY (food_id) REFERENCES food_items (id),
+    UNIQUE (entry_id, food_id)
+);
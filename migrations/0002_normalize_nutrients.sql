@@ -0,0 +1,335 @@
+-- Version 2: move the 99 per-nutrient columns of food_items into a nutrients
+-- dictionary and a food_nutrients(food_id, nutrient_id, amount) table. A
+-- nutrient a food has no row for is "not reported", which the old NOT NULL
+-- columns could not express.
+
+CREATE TABLE nutrients (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    key TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    unit TEXT,
+    attr_id INTEGER UNIQUE
+);
+
+INSERT INTO nutrients (key, name, unit, attr_id) VALUES
+    ('calcium_ca', 'Calcium, Ca', 'mg', 301),
+    ('carbohydrates', 'Carbohydrate, by difference', 'g', 205),
+    ('cholesterol', 'Cholesterol', 'mg', 601),
+    ('energy', 'Energy', 'kcal', 208),
+    ('fatty_acids_saturated', 'Fatty acids, total saturated', 'g', 606),
+    ('total_lipid_fat', 'Total lipid (fat)', 'g', 204),
+    ('fatty_acids_trans', 'Fatty acids, total trans', 'g', 605),
+    ('iron_fe', 'Iron, Fe', 'mg', 303),
+    ('fiber_dietary', 'Fiber, total dietary', 'g', 291),
+    ('potassium_k', 'Potassium, K', 'mg', 306),
+    ('sodium_na', 'Sodium, Na', 'mg', 307),
+    ('protein', 'Protein', 'g', 203),
+    ('sugars_total', 'Sugars, total', 'g', 269),
+    ('sugars_added', 'Sugars, added', 'g', 539),
+    ('vitamin_d', 'Vitamin D', 'IU', 324),
+    ('alanine', 'Alanine', 'g', 513),
+    ('alcohol_ethyl', 'Alcohol, ethyl', 'g', 221),
+    ('arginine', 'Arginine', 'g', 511),
+    ('ash', 'Ash', 'g', 207),
+    ('aspartic_acid', 'Aspartic acid', 'g', 514),
+    ('betaine', 'Betaine', 'mg', 454),
+    ('caffeine', 'Caffeine', 'mg', 262),
+    ('campesterol', 'Campesterol', 'mg', 639),
+    ('carotene_alpha', 'Carotene, alpha', 'µg', 322),
+    ('carotene_beta', 'Carotene, beta', 'µg', 321),
+    ('vitamin_d3', 'Vitamin D3 (cholecalciferol)', 'µg', 326),
+    ('choline_total', 'Choline, total', 'mg', 421),
+    ('cryptoxanthin_beta', 'Cryptoxanthin, beta', 'µg', 334),
+    ('copper_cu', 'Copper, Cu', 'mg', 312),
+    ('cystine', 'Cystine', 'g', 507),
+    ('energy_kj', 'Energy (kJ)', 'kJ', 268),
+    ('vitamin_d2', 'Vitamin D2 (ergocalciferol)', 'µg', 325),
+    ('fatty_acids_monounsaturated', 'Fatty acids, total monounsaturated', 'g', 645),
+    ('fatty_acids_polyunsaturated', 'Fatty acids, total polyunsaturated', 'g', 646),
+    ('fatty_acids_transmonoenoic', 'Fatty acids, total trans-monoenoic', 'g', 693),
+    ('fatty_acids_transpolyenoic', 'Fatty acids, total trans-polyenoic', 'g', 695),
+    ('fluoride_f', 'Fluoride, F', 'µg', 313),
+    ('folate_total', 'Folate, total', 'µg', 417),
+    ('folic_acid', 'Folic acid', 'µg', 431),
+    ('folate_dfe', 'Folate, DFE', 'µg', 435),
+    ('folate_food', 'Folate, food', 'µg', 432),
+    ('fructose', 'Fructose', 'g', 212),
+    ('galactose', 'Galactose', 'g', 287),
+    ('glutamic_acid', 'Glutamic acid', 'g', 515),
+    ('glucose_dextrose', 'Glucose (dextrose)', 'g', 211),
+    ('glycine', 'Glycine', 'g', 516),
+    ('histidine', 'Histidine', 'g', 512),
+    ('hydroxyproline', 'Hydroxyproline', 'g', 521),
+    ('isoleucine', 'Isoleucine', 'g', 503),
+    ('lactose', 'Lactose', 'g', 213),
+    ('leucine', 'Leucine', 'g', 504),
+    ('lutein_zeaxanthin', 'Lutein + zeaxanthin', 'µg', 338),
+    ('lycopene', 'Lycopene', 'µg', 337),
+    ('lysine', 'Lysine', 'g', 505),
+    ('maltose', 'Maltose', 'g', 214),
+    ('methionine', 'Methionine', 'g', 506),
+    ('magnesium_mg', 'Magnesium, Mg', 'mg', 304),
+    ('menaquinone', 'Menaquinone-4', 'µg', 428),
+    ('manganese_mn', 'Manganese, Mn', 'mg', 315),
+    ('niacin', 'Niacin', 'mg', 406),
+    ('vitamin_e_added', 'Vitamin E, added', 'mg', 573),
+    ('vitamin_b_added', 'Vitamin B-12, added', 'µg', 578),
+    ('adjusted_protein', 'Adjusted protein', 'g', 257),
+    ('phosphorus_p', 'Phosphorus, P', 'mg', 305),
+    ('pantothenic_acid', 'Pantothenic acid', 'mg', 410),
+    ('phenylalanine', 'Phenylalanine', 'g', 508),
+    ('phytosterols', 'Phytosterols', 'mg', 636),
+    ('proline', 'Proline', 'g', 517),
+    ('retinol', 'Retinol', 'µg', 319),
+    ('riboflavin', 'Riboflavin', 'mg', 405),
+    ('selenium_se', 'Selenium, Se', 'µg', 317),
+    ('serine', 'Serine', 'g', 518),
+    ('betasitosterol', 'Beta-sitosterol', 'mg', 641),
+    ('starch', 'Starch', 'g', 209),
+    ('stigmasterol', 'Stigmasterol', 'mg', 638),
+    ('sucrose', 'Sucrose', 'g', 210),
+    ('theobromine', 'Theobromine', 'mg', 263),
+    ('thiamin', 'Thiamin', 'mg', 404),
+    ('threonine', 'Threonine', 'g', 502),
+    ('vitamin_e_alphatocopherol', 'Vitamin E (alpha-tocopherol)', 'mg', 323),
+    ('tocopherol_beta', 'Tocopherol, beta', 'mg', 341),
+    ('tocopherol_delta', 'Tocopherol, delta', 'mg', 343),
+    ('tocopherol_gamma', 'Tocopherol, gamma', 'mg', 342),
+    ('tryptophan', 'Tryptophan', 'g', 501),
+    ('tyrosine', 'Tyrosine', 'g', 509),
+    ('valine', 'Valine', 'g', 510),
+    ('vitamin_a_iu', 'Vitamin A, IU', 'IU', 318),
+    ('vitamin_a_rae', 'Vitamin A, RAE', 'µg', 320),
+    ('vitamin_b12', 'Vitamin B-12', 'µg', 418),
+    ('vitamin_b6', 'Vitamin B-6', 'mg', 415),
+    ('vitamin_c_total_ascorbic_acid', 'Vitamin C, total ascorbic acid', 'mg', 401),
+    ('vitamin_k_phylloquinone', 'Vitamin K (phylloquinone)', 'µg', 430),
+    ('dihydrophylloquinone', 'Dihydrophylloquinone', 'µg', 429),
+    ('water', 'Water', 'g', 255),
+    ('zinc_zn', 'Zinc, Zn', 'mg', 309),
+    ('tocotrienol_alpha', 'Tocotrienol, alpha', 'mg', 344),
+    ('tocotrienol_beta', 'Tocotrienol, beta', 'mg', 345),
+    ('tocotrienol_gamma', 'Tocotrienol, gamma', 'mg', 346),
+    ('tocotrienol_delta', 'Tocotrienol, delta', 'mg', 347);
+
+CREATE TABLE food_nutrients (
+    food_id INTEGER NOT NULL,
+    nutrient_id INTEGER NOT NULL,
+    amount REAL NOT NULL,
+    PRIMARY KEY (food_id, nutrient_id),
+    FOREIGN KEY (food_id) REFERENCES food_items (id),
+    FOREIGN KEY (nutrient_id) REFERENCES nutrients (id)
+);
+
+-- Existing rows were written with 0.0 standing in for missing values, so
+-- there is no way to tell those apart here; every column is carried over.
+INSERT INTO food_nutrients (food_id, nutrient_id, amount)
+    SELECT f.id, n.id, f.calcium_ca FROM food_items f JOIN nutrients n ON n.key = 'calcium_ca'
+    UNION ALL
+    SELECT f.id, n.id, f.carbohydrates FROM food_items f JOIN nutrients n ON n.key = 'carbohydrates'
+    UNION ALL
+    SELECT f.id, n.id, f.cholesterol FROM food_items f JOIN nutrients n ON n.key = 'cholesterol'
+    UNION ALL
+    SELECT f.id, n.id, f.energy FROM food_items f JOIN nutrients n ON n.key = 'energy'
+    UNION ALL
+    SELECT f.id, n.id, f.fatty_acids_saturated FROM food_items f JOIN nutrients n ON n.key = 'fatty_acids_saturated'
+    UNION ALL
+    SELECT f.id, n.id, f.total_lipid_fat FROM food_items f JOIN nutrients n ON n.key = 'total_lipid_fat'
+    UNION ALL
+    SELECT f.id, n.id, f.fatty_acids_trans FROM food_items f JOIN nutrients n ON n.key = 'fatty_acids_trans'
+    UNION ALL
+    SELECT f.id, n.id, f.iron_fe FROM food_items f JOIN nutrients n ON n.key = 'iron_fe'
+    UNION ALL
+    SELECT f.id, n.id, f.fiber_dietary FROM food_items f JOIN nutrients n ON n.key = 'fiber_dietary'
+    UNION ALL
+    SELECT f.id, n.id, f.potassium_k FROM food_items f JOIN nutrients n ON n.key = 'potassium_k'
+    UNION ALL
+    SELECT f.id, n.id, f.sodium_na FROM food_items f JOIN nutrients n ON n.key = 'sodium_na'
+    UNION ALL
+    SELECT f.id, n.id, f.protein FROM food_items f JOIN nutrients n ON n.key = 'protein'
+    UNION ALL
+    SELECT f.id, n.id, f.sugars_total FROM food_items f JOIN nutrients n ON n.key = 'sugars_total'
+    UNION ALL
+    SELECT f.id, n.id, f.sugars_added FROM food_items f JOIN nutrients n ON n.key = 'sugars_added'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_d FROM food_items f JOIN nutrients n ON n.key = 'vitamin_d'
+    UNION ALL
+    SELECT f.id, n.id, f.alanine FROM food_items f JOIN nutrients n ON n.key = 'alanine'
+    UNION ALL
+    SELECT f.id, n.id, f.alcohol_ethyl FROM food_items f JOIN nutrients n ON n.key = 'alcohol_ethyl'
+    UNION ALL
+    SELECT f.id, n.id, f.arginine FROM food_items f JOIN nutrients n ON n.key = 'arginine'
+    UNION ALL
+    SELECT f.id, n.id, f.ash FROM food_items f JOIN nutrients n ON n.key = 'ash'
+    UNION ALL
+    SELECT f.id, n.id, f.aspartic_acid FROM food_items f JOIN nutrients n ON n.key = 'aspartic_acid'
+    UNION ALL
+    SELECT f.id, n.id, f.betaine FROM food_items f JOIN nutrients n ON n.key = 'betaine'
+    UNION ALL
+    SELECT f.id, n.id, f.caffeine FROM food_items f JOIN nutrients n ON n.key = 'caffeine'
+    UNION ALL
+    SELECT f.id, n.id, f.campesterol FROM food_items f JOIN nutrients n ON n.key = 'campesterol'
+    UNION ALL
+    SELECT f.id, n.id, f.carotene_alpha FROM food_items f JOIN nutrients n ON n.key = 'carotene_alpha'
+    UNION ALL
+    SELECT f.id, n.id, f.carotene_beta FROM food_items f JOIN nutrients n ON n.key = 'carotene_beta'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_d3 FROM food_items f JOIN nutrients n ON n.key = 'vitamin_d3'
+    UNION ALL
+    SELECT f.id, n.id, f.choline_total FROM food_items f JOIN nutrients n ON n.key = 'choline_total'
+    UNION ALL
+    SELECT f.id, n.id, f.cryptoxanthin_beta FROM food_items f JOIN nutrients n ON n.key = 'cryptoxanthin_beta'
+    UNION ALL
+    SELECT f.id, n.id, f.copper_cu FROM food_items f JOIN nutrients n ON n.key = 'copper_cu'
+    UNION ALL
+    SELECT f.id, n.id, f.cystine FROM food_items f JOIN nutrients n ON n.key = 'cystine'
+    UNION ALL
+    SELECT f.id, n.id, f.energy_kj FROM food_items f JOIN nutrients n ON n.key = 'energy_kj'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_d2 FROM food_items f JOIN nutrients n ON n.key = 'vitamin_d2'
+    UNION ALL
+    SELECT f.id, n.id, f.fatty_acids_monounsaturated FROM food_items f JOIN nutrients n ON n.key = 'fatty_acids_monounsaturated'
+    UNION ALL
+    SELECT f.id, n.id, f.fatty_acids_polyunsaturated FROM food_items f JOIN nutrients n ON n.key = 'fatty_acids_polyunsaturated'
+    UNION ALL
+    SELECT f.id, n.id, f.fatty_acids_transmonoenoic FROM food_items f JOIN nutrients n ON n.key = 'fatty_acids_transmonoenoic'
+    UNION ALL
+    SELECT f.id, n.id, f.fatty_acids_transpolyenoic FROM food_items f JOIN nutrients n ON n.key = 'fatty_acids_transpolyenoic'
+    UNION ALL
+    SELECT f.id, n.id, f.fluoride_f FROM food_items f JOIN nutrients n ON n.key = 'fluoride_f'
+    UNION ALL
+    SELECT f.id, n.id, f.folate_total FROM food_items f JOIN nutrients n ON n.key = 'folate_total'
+    UNION ALL
+    SELECT f.id, n.id, f.folic_acid FROM food_items f JOIN nutrients n ON n.key = 'folic_acid'
+    UNION ALL
+    SELECT f.id, n.id, f.folate_dfe FROM food_items f JOIN nutrients n ON n.key = 'folate_dfe'
+    UNION ALL
+    SELECT f.id, n.id, f.folate_food FROM food_items f JOIN nutrients n ON n.key = 'folate_food'
+    UNION ALL
+    SELECT f.id, n.id, f.fructose FROM food_items f JOIN nutrients n ON n.key = 'fructose'
+    UNION ALL
+    SELECT f.id, n.id, f.galactose FROM food_items f JOIN nutrients n ON n.key = 'galactose'
+    UNION ALL
+    SELECT f.id, n.id, f.glutamic_acid FROM food_items f JOIN nutrients n ON n.key = 'glutamic_acid'
+    UNION ALL
+    SELECT f.id, n.id, f.glucose_dextrose FROM food_items f JOIN nutrients n ON n.key = 'glucose_dextrose'
+    UNION ALL
+    SELECT f.id, n.id, f.glycine FROM food_items f JOIN nutrients n ON n.key = 'glycine'
+    UNION ALL
+    SELECT f.id, n.id, f.histidine FROM food_items f JOIN nutrients n ON n.key = 'histidine'
+    UNION ALL
+    SELECT f.id, n.id, f.hydroxyproline FROM food_items f JOIN nutrients n ON n.key = 'hydroxyproline'
+    UNION ALL
+    SELECT f.id, n.id, f.isoleucine FROM food_items f JOIN nutrients n ON n.key = 'isoleucine'
+    UNION ALL
+    SELECT f.id, n.id, f.lactose FROM food_items f JOIN nutrients n ON n.key = 'lactose'
+    UNION ALL
+    SELECT f.id, n.id, f.leucine FROM food_items f JOIN nutrients n ON n.key = 'leucine'
+    UNION ALL
+    SELECT f.id, n.id, f.lutein_zeaxanthin FROM food_items f JOIN nutrients n ON n.key = 'lutein_zeaxanthin'
+    UNION ALL
+    SELECT f.id, n.id, f.lycopene FROM food_items f JOIN nutrients n ON n.key = 'lycopene'
+    UNION ALL
+    SELECT f.id, n.id, f.lysine FROM food_items f JOIN nutrients n ON n.key = 'lysine'
+    UNION ALL
+    SELECT f.id, n.id, f.maltose FROM food_items f JOIN nutrients n ON n.key = 'maltose'
+    UNION ALL
+    SELECT f.id, n.id, f.methionine FROM food_items f JOIN nutrients n ON n.key = 'methionine'
+    UNION ALL
+    SELECT f.id, n.id, f.magnesium_mg FROM food_items f JOIN nutrients n ON n.key = 'magnesium_mg'
+    UNION ALL
+    SELECT f.id, n.id, f.menaquinone FROM food_items f JOIN nutrients n ON n.key = 'menaquinone'
+    UNION ALL
+    SELECT f.id, n.id, f.manganese_mn FROM food_items f JOIN nutrients n ON n.key = 'manganese_mn'
+    UNION ALL
+    SELECT f.id, n.id, f.niacin FROM food_items f JOIN nutrients n ON n.key = 'niacin'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_e_added FROM food_items f JOIN nutrients n ON n.key = 'vitamin_e_added'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_b_added FROM food_items f JOIN nutrients n ON n.key = 'vitamin_b_added'
+    UNION ALL
+    SELECT f.id, n.id, f.adjusted_protein FROM food_items f JOIN nutrients n ON n.key = 'adjusted_protein'
+    UNION ALL
+    SELECT f.id, n.id, f.phosphorus_p FROM food_items f JOIN nutrients n ON n.key = 'phosphorus_p'
+    UNION ALL
+    SELECT f.id, n.id, f.pantothenic_acid FROM food_items f JOIN nutrients n ON n.key = 'pantothenic_acid'
+    UNION ALL
+    SELECT f.id, n.id, f.phenylalanine FROM food_items f JOIN nutrients n ON n.key = 'phenylalanine'
+    UNION ALL
+    SELECT f.id, n.id, f.phytosterols FROM food_items f JOIN nutrients n ON n.key = 'phytosterols'
+    UNION ALL
+    SELECT f.id, n.id, f.proline FROM food_items f JOIN nutrients n ON n.key = 'proline'
+    UNION ALL
+    SELECT f.id, n.id, f.retinol FROM food_items f JOIN nutrients n ON n.key = 'retinol'
+    UNION ALL
+    SELECT f.id, n.id, f.riboflavin FROM food_items f JOIN nutrients n ON n.key = 'riboflavin'
+    UNION ALL
+    SELECT f.id, n.id, f.selenium_se FROM food_items f JOIN nutrients n ON n.key = 'selenium_se'
+    UNION ALL
+    SELECT f.id, n.id, f.serine FROM food_items f JOIN nutrients n ON n.key = 'serine'
+    UNION ALL
+    SELECT f.id, n.id, f.betasitosterol FROM food_items f JOIN nutrients n ON n.key = 'betasitosterol'
+    UNION ALL
+    SELECT f.id, n.id, f.starch FROM food_items f JOIN nutrients n ON n.key = 'starch'
+    UNION ALL
+    SELECT f.id, n.id, f.stigmasterol FROM food_items f JOIN nutrients n ON n.key = 'stigmasterol'
+    UNION ALL
+    SELECT f.id, n.id, f.sucrose FROM food_items f JOIN nutrients n ON n.key = 'sucrose'
+    UNION ALL
+    SELECT f.id, n.id, f.theobromine FROM food_items f JOIN nutrients n ON n.key = 'theobromine'
+    UNION ALL
+    SELECT f.id, n.id, f.thiamin FROM food_items f JOIN nutrients n ON n.key = 'thiamin'
+    UNION ALL
+    SELECT f.id, n.id, f.threonine FROM food_items f JOIN nutrients n ON n.key = 'threonine'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_e_alphatocopherol FROM food_items f JOIN nutrients n ON n.key = 'vitamin_e_alphatocopherol'
+    UNION ALL
+    SELECT f.id, n.id, f.tocopherol_beta FROM food_items f JOIN nutrients n ON n.key = 'tocopherol_beta'
+    UNION ALL
+    SELECT f.id, n.id, f.tocopherol_delta FROM food_items f JOIN nutrients n ON n.key = 'tocopherol_delta'
+    UNION ALL
+    SELECT f.id, n.id, f.tocopherol_gamma FROM food_items f JOIN nutrients n ON n.key = 'tocopherol_gamma'
+    UNION ALL
+    SELECT f.id, n.id, f.tryptophan FROM food_items f JOIN nutrients n ON n.key = 'tryptophan'
+    UNION ALL
+    SELECT f.id, n.id, f.tyrosine FROM food_items f JOIN nutrients n ON n.key = 'tyrosine'
+    UNION ALL
+    SELECT f.id, n.id, f.valine FROM food_items f JOIN nutrients n ON n.key = 'valine'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_a_iu FROM food_items f JOIN nutrients n ON n.key = 'vitamin_a_iu'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_a_rae FROM food_items f JOIN nutrients n ON n.key = 'vitamin_a_rae'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_b12 FROM food_items f JOIN nutrients n ON n.key = 'vitamin_b12'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_b6 FROM food_items f JOIN nutrients n ON n.key = 'vitamin_b6'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_c_total_ascorbic_acid FROM food_items f JOIN nutrients n ON n.key = 'vitamin_c_total_ascorbic_acid'
+    UNION ALL
+    SELECT f.id, n.id, f.vitamin_k_phylloquinone FROM food_items f JOIN nutrients n ON n.key = 'vitamin_k_phylloquinone'
+    UNION ALL
+    SELECT f.id, n.id, f.dihydrophylloquinone FROM food_items f JOIN nutrients n ON n.key = 'dihydrophylloquinone'
+    UNION ALL
+    SELECT f.id, n.id, f.water FROM food_items f JOIN nutrients n ON n.key = 'water'
+    UNION ALL
+    SELECT f.id, n.id, f.zinc_zn FROM food_items f JOIN nutrients n ON n.key = 'zinc_zn'
+    UNION ALL
+    SELECT f.id, n.id, f.tocotrienol_alpha FROM food_items f JOIN nutrients n ON n.key = 'tocotrienol_alpha'
+    UNION ALL
+    SELECT f.id, n.id, f.tocotrienol_beta FROM food_items f JOIN nutrients n ON n.key = 'tocotrienol_beta'
+    UNION ALL
+    SELECT f.id, n.id, f.tocotrienol_gamma FROM food_items f JOIN nutrients n ON n.key = 'tocotrienol_gamma'
+    UNION ALL
+    SELECT f.id, n.id, f.tocotrienol_delta FROM food_items f JOIN nutrients n ON n.key = 'tocotrienol_delta';
+
+CREATE TABLE food_items_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    weight_grams REAL NOT NULL
+);
+
+INSERT INTO food_items_new (id, name, weight_grams)
+    SELECT id, name, weight_grams FROM food_items;
+
+DROP TABLE food_items;
+ALTER TABLE food_items_new RENAME TO food_items;
@@ -0,0 +1,12 @@
+-- Version 3: provider responses keyed by normalized query text, so repeated
+-- lookups don't spend API quota and seen queries still answer offline.
+
+CREATE TABLE lookup_cache (
+    provider TEXT NOT NULL,
+    query TEXT NOT NULL,
+    -- JSON array of foods as the provider reported them.
+    response TEXT NOT NULL,
+    -- Unix seconds.
+    fetched_at INTEGER NOT NULL,
+    PRIMARY KEY (provider, query)
+);
@@ -0,0 +1,21 @@
+-- Version 4: queries that couldn't be looked up (usually because the app was
+-- offline), kept until the provider answers them.
+
+CREATE TABLE pending_lookups (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    provider TEXT NOT NULL,
+    query TEXT NOT NULL,
+    -- Unix seconds.
+    queued_at INTEGER NOT NULL,
+    -- Placeholder entry the resolved foods are added to, if one was logged.
+    entry_id INTEGER REFERENCES entries(id),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    -- Set when the provider answered but couldn't resolve the query, so it
+    -- isn't retried until the user asks.
+    stalled INTEGER NOT NULL DEFAULT 0
+);
+
+-- Queries are stored normalized, so asking again while one is still waiting
+-- finds it instead of queueing a second lookup.
+CREATE UNIQUE INDEX pending_lookups_query ON pending_lookups (provider, query);
@@ -0,0 +1,11 @@
+-- Version 5: where a food came from and how it is usually portioned, so
+-- packaged foods can be found by barcode.
+
+ALTER TABLE food_items ADD COLUMN brand TEXT;
+-- Digits only, as normalize_barcode in provider.rs produces them.
+ALTER TABLE food_items ADD COLUMN barcode TEXT;
+-- A typical serving as the source describes it, e.g. "30 g (2 biscuits)".
+ALTER TABLE food_items ADD COLUMN serving_label TEXT;
+ALTER TABLE food_items ADD COLUMN serving_grams REAL;
+
+CREATE INDEX food_items_barcode ON food_items (barcode);
@@ -0,0 +1,37 @@
+-- Version 6: store every food's nutrient amounts per 100 g rather than per
+-- whatever weight its source reported them for, and drop that weight. It is
+-- kept as the food's serving where the food had none. Nothing records which
+-- sources reported per serving, but Nutritionix, the one that did without
+-- naming a serving separately, always labelled its serving, so a 100 g
+-- weight is taken as a serving only when it came with a label. A per-100 g
+-- import whose serving was given only by volume is taken as one too.
+
+-- Amounts for no weight at all can't be converted, so they become unknown.
+DELETE FROM food_nutrients
+    WHERE food_id IN (SELECT id FROM food_items WHERE weight_grams <= 0);
+
+UPDATE food_nutrients
+    SET amount = amount * 100.0 / (SELECT weight_grams FROM food_items WHERE id = food_nutrients.food_id)
+    WHERE food_id IN (SELECT id FROM food_items WHERE weight_grams > 0 AND weight_grams <> 100);
+
+UPDATE food_items
+    SET serving_grams = weight_grams
+    WHERE serving_grams IS NULL AND weight_grams > 0
+        AND (weight_grams <> 100 OR serving_label IS NOT NULL);
+
+CREATE TABLE food_items_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    brand TEXT,
+    barcode TEXT,
+    serving_label TEXT,
+    serving_grams REAL
+);
+
+INSERT INTO food_items_new (id, name, brand, barcode, serving_label, serving_grams)
+    SELECT id, name, brand, barcode, serving_label, serving_grams FROM food_items;
+
+DROP TABLE food_items;
+ALTER TABLE food_items_new RENAME TO food_items;
+
+CREATE INDEX food_items_barcode ON food_items (barcode);
@@ -0,0 +1,13 @@
+-- Version 7: household measures per food, such as a "slice" of bread or a
+-- "cup" of rice, so portions can be entered that way and stored in grams. A
+-- measure named "ml" gives the food's density in grams per millilitre.
+
+CREATE TABLE food_measures (
+    food_id INTEGER NOT NULL,
+    -- Lowercase, as measures::Measure::new produces it.
+    name TEXT NOT NULL,
+    -- Weight of one of it.
+    grams REAL NOT NULL,
+    PRIMARY KEY (food_id, name),
+    FOREIGN KEY (food_id) REFERENCES food_items (id)
+);
@@ -0,0 +1,44 @@
+-- Version 8: other names a food goes by, and a full-text index over names,
+-- brands and those aliases for searching foods as they're typed. Triggers
+-- keep the index in step with every write, including the command line ones.
+
+CREATE TABLE food_aliases (
+    food_id INTEGER NOT NULL,
+    alias TEXT NOT NULL,
+    PRIMARY KEY (food_id, alias),
+    FOREIGN KEY (food_id) REFERENCES food_items (id)
+);
+
+-- One row per food, with the food's id as its rowid.
+CREATE VIRTUAL TABLE food_search USING fts5(name, brand, aliases);
+
+-- Every distinct word in the index, for finding what a misspelt one meant.
+CREATE VIRTUAL TABLE food_search_terms USING fts5vocab(food_search, 'row');
+
+INSERT INTO food_search (rowid, name, brand, aliases)
+    SELECT id, name, COALESCE(brand, ''), '' FROM food_items;
+
+CREATE TRIGGER food_search_insert AFTER INSERT ON food_items BEGIN
+    INSERT INTO food_search (rowid, name, brand, aliases)
+        VALUES (NEW.id, NEW.name, COALESCE(NEW.brand, ''), '');
+END;
+
+CREATE TRIGGER food_search_update AFTER UPDATE OF name, brand ON food_items BEGIN
+    UPDATE food_search SET name = NEW.name, brand = COALESCE(NEW.brand, '') WHERE rowid = NEW.id;
+END;
+
+CREATE TRIGGER food_search_delete AFTER DELETE ON food_items BEGIN
+    DELETE FROM food_search WHERE rowid = OLD.id;
+END;
+
+CREATE TRIGGER food_aliases_insert AFTER INSERT ON food_aliases BEGIN
+    UPDATE food_search
+        SET aliases = (SELECT group_concat(alias, ' ') FROM food_aliases WHERE food_id = NEW.food_id)
+        WHERE rowid = NEW.food_id;
+END;
+
+CREATE TRIGGER food_aliases_delete AFTER DELETE ON food_aliases BEGIN
+    UPDATE food_search
+        SET aliases = COALESCE((SELECT group_concat(alias, ' ') FROM food_aliases WHERE food_id = OLD.food_id), '')
+        WHERE rowid = OLD.food_id;
+END;
@@ -0,0 +1,262 @@
+// Reads EAN-13 and UPC-A barcodes out of photos or scans. Each of several
+// rows across the image is thresholded into runs of dark and light, and every
+// window of runs shaped like a barcode is decoded and checksummed.
+
+use image::GrayImage;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+#[derive(Debug, Error)]
+pub enum BarcodeError {
+    #[error("Could not open the image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("No EAN-13 or UPC-A barcode found in the image")]
+    NotFound,
+}
+
+// Module widths of each digit's space-bar-space-bar in the "L" encoding. The
+// "R" encoding has the same widths starting with a bar, and "G" is L reversed.
+const L_PATTERNS: [[u8; 4]; 10] = [
+    [3, 2, 1, 1],
+    [2, 2, 2, 1],
+    [2, 1, 2, 2],
+    [1, 4, 1, 1],
+    [1, 1, 3, 2],
+    [1, 2, 3, 1],
+    [1, 1, 1, 4],
+    [1, 3, 1, 2],
+    [1, 2, 1, 3],
+    [3, 1, 1, 2],
+];
+
+// Which of the six left-hand digits use G rather than L (bit 5 is the first
+// digit), indexed by the leading digit they encode.
+const FIRST_DIGIT_PARITY: [u8; 10] = [
+    0b000000, 0b001011, 0b001101, 0b001110, 0b010011, 0b011001, 0b011100, 0b010101, 0b010110, 0b011010,
+];
+
+// Guard, 6 digits, middle guard, 6 digits, guard.
+const RUNS_PER_CODE: usize = 3 + 6 * 4 + 5 + 6 * 4 + 3;
+const MODULES_PER_CODE: f32 = 95.0;
+const SCAN_LINES: u32 = 25;
+// Average mismatch per digit, in modules, beyond which a match is rejected.
+const MAX_DIGIT_ERROR: f32 = 1.6;
+
+pub fn decode_file(path: &Path) -> Result<String, BarcodeError> {
+    let image = image::open(path)?.to_luma8();
+    decode(&image).ok_or(BarcodeError::NotFound)
+}
+
+// Photos take a moment to decode, so this keeps it off the UI thread.
+pub async fn decode_file_in_background(path: PathBuf) -> Result<String, BarcodeError> {
+    let (tx, rx) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(decode_file(&path));
+    });
+    rx.await.unwrap_or(Err(BarcodeError::NotFound))
+}
+
+pub fn decode(image: &GrayImage) -> Option<String> {
+    let (width, height) = image.dimensions();
+
+    // Middle rows first, where a centred barcode is most likely.
+    let mut rows: Vec<u32> = (1..=SCAN_LINES).map(|line| line * height / (SCAN_LINES + 1)).collect();
+    rows.sort_by_key(|row| row.abs_diff(height / 2));
+
+    for row in rows {
+        let luma: Vec<u8> = (0..width).map(|x| image.get_pixel(x, row)[0]).collect();
+        let runs = runs(&luma);
+
+        if let Some(code) = decode_runs(&runs) {
+            return Some(code);
+        }
+        // Upside-down scans read right to left.
+        let reversed: Vec<Run> = runs.iter().rev().copied().collect();
+        if let Some(code) = decode_runs(&reversed) {
+            return Some(code);
+        }
+    }
+
+    None
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    dark: bool,
+    width: u32,
+}
+
+// Splits a row at the midpoint between its darkest and lightest pixels.
+fn runs(luma: &[u8]) -> Vec<Run> {
+    let (min, max) = luma
+        .iter()
+        .fold((u8::MAX, u8::MIN), |(min, max), &value| (min.min(value), max.max(value)));
+    if max.saturating_sub(min) < 32 {
+        return Vec::new();
+    }
+    let threshold = (min as u16 + max as u16) / 2;
+
+    let mut runs: Vec<Run> = Vec::new();
+    for &value in luma {
+        let dark = (value as u16) < threshold;
+        match runs.last_mut() {
+            Some(run) if run.dark == dark => run.width += 1,
+            _ => runs.push(Run { dark, width: 1 }),
+        }
+    }
+    runs
+}
+
+fn decode_runs(runs: &[Run]) -> Option<String> {
+    if runs.len() < RUNS_PER_CODE {
+        return None;
+    }
+
+    (0..=runs.len() - RUNS_PER_CODE)
+        .filter(|&start| runs[start].dark)
+        .find_map(|start| decode_window(&runs[start..start + RUNS_PER_CODE]))
+}
+
+fn decode_window(window: &[Run]) -> Option<String> {
+    let total: u32 = window.iter().map(|run| run.width).sum();
+    let module = total as f32 / MODULES_PER_CODE;
+
+    let is_guard = |runs: &[Run]| runs.iter().all(|run| (run.width as f32 / module - 1.0).abs() < 0.7);
+    if !is_guard(&window[0..3]) || !is_guard(&window[27..32]) || !is_guard(&window[56..59]) {
+        return None;
+    }
+
+    let mut digits = [0u8; 13];
+    let mut parity = 0u8;
+
+    for (index, digit_runs) in window[3..27].chunks(4).enumerate() {
+        let (digit, is_g) = match_digit(digit_runs, true)?;
+        digits[index + 1] = digit;
+        if is_g {
+            parity |= 1 << (5 - index);
+        }
+    }
+    for (index, digit_runs) in window[32..56].chunks(4).enumerate() {
+        let (digit, is_g) = match_digit(digit_runs, false)?;
+        if is_g {
+            return None;
+        }
+        digits[index + 7] = digit;
+    }
+
+    digits[0] = FIRST_DIGIT_PARITY.iter().position(|&pattern| pattern == parity)? as u8;
+
+    if !checksum_ok(&digits) {
+        return None;
+    }
+    Some(digits.iter().map(|digit| char::from(b'0' + digit)).collect())
+}
+
+// Best L/R or G pattern for four runs, scaled so they span seven modules.
+// G patterns only occur on the left half.
+fn match_digit(runs: &[Run], left_half: bool) -> Option<(u8, bool)> {
+    let total: u32 = runs.iter().map(|run| run.width).sum();
+    let scaled: Vec<f32> = runs.iter().map(|run| run.width as f32 * 7.0 / total as f32).collect();
+
+    let error = |pattern: [u8; 4]| -> f32 {
+        scaled
+            .iter()
+            .zip(pattern)
+            .map(|(width, modules)| (width - modules as f32).abs())
+            .sum()
+    };
+
+    let mut best: Option<(f32, u8, bool)> = None;
+    for (digit, pattern) in L_PATTERNS.iter().enumerate() {
+        let mut candidates = vec![(error(*pattern), false)];
+        if left_half {
+            let mut reversed = *pattern;
+            reversed.reverse();
+            candidates.push((error(reversed), true));
+        }
+
+        for (candidate_error, is_g) in candidates {
+            if best.is_none_or(|(best_error, _, _)| candidate_error < best_error) {
+                best = Some((candidate_error, digit as u8, is_g));
+            }
+        }
+    }
+
+    best.filter(|(error, _, _)| *error < MAX_DIGIT_ERROR)
+        .map(|(_, digit, is_g)| (digit, is_g))
+}
+
+fn checksum_ok(digits: &[u8; 13]) -> bool {
+    let sum: u32 = digits[..12]
+        .iter()
+        .enumerate()
+        .map(|(index, &digit)| digit as u32 * if index % 2 == 0 { 1 } else { 3 })
+        .sum();
+    (10 - sum % 10) % 10 == digits[12] as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    // Draws an EAN-13 with the given module width and a quiet zone either side.
+    fn render(code: &str, module: u32) -> GrayImage {
+        let digits: Vec<u8> = code.bytes().map(|byte| byte - b'0').collect();
+        let parity = FIRST_DIGIT_PARITY[digits[0] as usize];
+
+        // Widths alternating bar, space, bar... from the start guard.
+        let mut widths: Vec<u8> = vec![1, 1, 1];
+        for (index, &digit) in digits[1..7].iter().enumerate() {
+            let mut pattern = L_PATTERNS[digit as usize];
+            if parity & (1 << (5 - index)) != 0 {
+                pattern.reverse();
+            }
+            widths.extend(pattern);
+        }
+        widths.extend([1, 1, 1, 1, 1]);
+        for &digit in &digits[7..] {
+            widths.extend(L_PATTERNS[digit as usize]);
+        }
+        widths.extend([1, 1, 1]);
+
+        let quiet = 10 * module;
+        let width = quiet * 2 + 95 * module;
+        let mut image = GrayImage::from_pixel(width, 40, Luma([255]));
+
+        // Start guard begins with a bar; after it, L digits begin with a space.
+        let mut x = quiet;
+        let mut dark = true;
+        for run in widths {
+            for _ in 0..run as u32 * module {
+                if dark {
+                    for y in 0..40 {
+                        image.put_pixel(x, y, Luma([20]));
+                    }
+                }
+                x += 1;
+            }
+            dark = !dark;
+        }
+        image
+    }
+
+    #[test]
+    fn decodes_rendered_ean13() {
+        assert_eq!(decode(&render("3017620422003", 3)).as_deref(), Some("3017620422003"));
+        // A UPC-A is an EAN-13 starting with 0.
+        assert_eq!(decode(&render("0041196010022", 2)).as_deref(), Some("0041196010022"));
+    }
+
+    #[test]
+    fn decodes_upside_down() {
+        let upside_down = image::imageops::rotate180(&render("5449000000996", 2));
+        assert_eq!(decode(&upside_down).as_deref(), Some("5449000000996"));
+    }
+
+    #[test]
+    fn rejects_blank_images() {
+        assert_eq!(decode(&GrayImage::from_pixel(300, 40, Luma([255]))), None);
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "spa-tracker";
+const CONFIG_FILE: &str = "config.json";
+const DEFAULT_DB_FILE: &str = "my_database.db";
+const DB_ENV_VAR: &str = "SPA_TRACKER_DB";
+const MAX_RECENT_DATABASES: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub db_path: Option<PathBuf>,
+    pub recent_databases: Vec<PathBuf>,
+    // Plain-text alternative to the encrypted store in credentials.rs.
+    pub nutritionix_app_id: Option<String>,
+    pub nutritionix_app_key: Option<String>,
+    // Network behaviour; anything unset falls back to the defaults in http.rs
+    // and nutrition_api.rs.
+    pub http_timeout_secs: Option<u64>,
+    pub http_connect_timeout_secs: Option<u64>,
+    pub http_max_retries: Option<u32>,
+    pub nutritionix_daily_quota: Option<u32>,
+    pub nutritionix_base_url: Option<String>,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(APP_DIR).join(CONFIG_FILE))
+    }
+
+    // A missing or unreadable config file just means "use the defaults".
+    pub fn load() -> Config {
+        Config::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Config::path().ok_or("No config directory on this platform")?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // Makes `path` the database opened on the next launch and moves it to the
+    // front of the recent list.
+    pub fn remember_database(&mut self, path: &Path) {
+        self.db_path = Some(path.to_path_buf());
+        self.recent_databases.retain(|recent| recent != path);
+        self.recent_databases.insert(0, path.to_path_buf());
+        self.recent_databases.truncate(MAX_RECENT_DATABASES);
+    }
+}
+
+// Picks the database file in order of precedence: the `--db` flag, the
+// SPA_TRACKER_DB environment variable, the config file, then the default.
+pub fn resolve_db_path() -> PathBuf {
+    if let Some(path) = db_flag(env::args().skip(1)) {
+        return path;
+    }
+
+    if let Some(path) = env::var_os(DB_ENV_VAR).filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+
+    if let Some(path) = Config::load().db_path {
+        return path;
+    }
+
+    default_db_path()
+}
+
+// `--in-memory` runs the app against a throwaway store instead of a file.
+pub fn in_memory_requested() -> bool {
+    env::args().skip(1).any(|arg| arg == "--in-memory")
+}
+
+fn db_flag(args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    let mut args = args;
+
+    while let Some(arg) = args.next() {
+        if arg == "--db" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--db=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+
+    None
+}
+
+// Per-user directory for the app's own files.
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_DIR))
+}
+
+pub fn default_db_path() -> PathBuf {
+    let legacy = PathBuf::from(DEFAULT_DB_FILE);
+
+    match data_dir() {
+        // Older builds always wrote to the working directory; keep using that
+        // file until there is one in the data directory.
+        Some(dir) => {
+            let path = dir.join(DEFAULT_DB_FILE);
+            if !path.exists() && legacy.exists() {
+                legacy
+            } else {
+                path
+            }
+        }
+        None => legacy,
+    }
+}
@@ -0,0 +1,177 @@
+use crate::config::{self, Config};
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const APP_ID_ENV_VAR: &str = "NUTRITIONIX_APP_ID";
+const APP_KEY_ENV_VAR: &str = "NUTRITIONIX_APP_KEY";
+const KEY_FILE: &str = "credentials.key";
+const STORE_FILE: &str = "credentials.enc";
+const NONCE_LEN: usize = 12;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Credentials {
+    pub app_id: String,
+    pub app_key: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CredentialSource {
+    Environment,
+    ConfigFile,
+    EncryptedStore,
+}
+
+impl CredentialSource {
+    pub fn describe(self) -> &'static str {
+        match self {
+            CredentialSource::Environment => "environment variables",
+            CredentialSource::ConfigFile => "config file",
+            CredentialSource::EncryptedStore => "encrypted local store",
+        }
+    }
+}
+
+impl Credentials {
+    // Environment variables win over the config file, which wins over
+    // whatever was saved from the Settings page.
+    pub fn resolve() -> Option<(Credentials, CredentialSource)> {
+        let from_env = (env::var(APP_ID_ENV_VAR).ok(), env::var(APP_KEY_ENV_VAR).ok());
+        if let Some(credentials) = Credentials::from_parts(from_env) {
+            return Some((credentials, CredentialSource::Environment));
+        }
+
+        let config = Config::load();
+        if let Some(credentials) = Credentials::from_parts((config.nutritionix_app_id, config.nutritionix_app_key)) {
+            return Some((credentials, CredentialSource::ConfigFile));
+        }
+
+        CredentialStore::default_location()
+            .and_then(|store| store.load().ok().flatten())
+            .map(|credentials| (credentials, CredentialSource::EncryptedStore))
+    }
+
+    fn from_parts(parts: (Option<String>, Option<String>)) -> Option<Credentials> {
+        match parts {
+            (Some(app_id), Some(app_key)) if !app_id.trim().is_empty() && !app_key.trim().is_empty() => {
+                Some(Credentials {
+                    app_id: app_id.trim().to_string(),
+                    app_key: app_key.trim().to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+// Credentials encrypted with ChaCha20-Poly1305 under a random key kept next
+// to them. This keeps the key out of config files and backups of them; it
+// is not a defence against someone who can read the whole data directory.
+pub struct CredentialStore {
+    dir: PathBuf,
+}
+
+impl CredentialStore {
+    pub fn new(dir: PathBuf) -> CredentialStore {
+        CredentialStore { dir }
+    }
+
+    pub fn default_location() -> Option<CredentialStore> {
+        config::data_dir().map(CredentialStore::new)
+    }
+
+    pub fn load(&self) -> Result<Option<Credentials>, Box<dyn Error>> {
+        let store_path = self.dir.join(STORE_FILE);
+        let key_path = self.dir.join(KEY_FILE);
+
+        if !store_path.exists() || !key_path.exists() {
+            return Ok(None);
+        }
+
+        let key = fs::read(key_path)?;
+        if key.len() != 32 {
+            return Err("Credential key file is corrupt".into());
+        }
+        let sealed = fs::read(store_path)?;
+        if sealed.len() < NONCE_LEN {
+            return Err("Credential store is corrupt".into());
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Could not decrypt the credential store")?;
+
+        Ok(Some(serde_json::from_slice(&plaintext)?))
+    }
+
+    pub fn save(&self, credentials: &Credentials) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+
+        let cipher = ChaCha20Poly1305::new(&self.key_or_create()?);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, serde_json::to_vec(credentials)?.as_slice())
+            .map_err(|_| "Could not encrypt credentials")?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        write_private(&self.dir.join(STORE_FILE), &sealed)?;
+
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<(), Box<dyn Error>> {
+        let store_path = self.dir.join(STORE_FILE);
+        if store_path.exists() {
+            fs::remove_file(store_path)?;
+        }
+        Ok(())
+    }
+
+    fn key_or_create(&self) -> Result<Key, Box<dyn Error>> {
+        let key_path = self.dir.join(KEY_FILE);
+
+        if let Ok(key) = fs::read(&key_path) {
+            if key.len() == 32 {
+                return Ok(*Key::from_slice(&key));
+            }
+        }
+
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        write_private(&key_path, &key)?;
+        Ok(key)
+    }
+}
+
+// Readable by the owner only from the moment it's created, since the key and
+// the ciphertext sit side by side. A file left from before with looser
+// permissions is tightened before anything is written to it.
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+
+    file.write_all(contents)?;
+    Ok(())
+}
@@ -1,16 +1,126 @@
-use crate::nutrition_api::{query_nutritionx_cli, get_nutrient_name_map, ApiFood, NutritionxResponse};
+use crate::provider::{normalize_barcode, AmountBasis, ProviderFood};
+use crate::lookup::{normalize_query, CachedLookup, PendingLookup};
+use crate::repository::{AddOutcome, ConflictStrategy};
+use crate::measures::{to_grams, Measure};
+use crate::food_search;
+use crate::migrations::run_migrations;
+use crate::nutrients::{NutrientKind, NUTRIENTS};
 
-use rusqlite::{params, Connection, Result, ToSql, Error as RusqliteError};
+use rusqlite::{params, Connection, Result, Error as RusqliteError};
 use chrono::prelude::*;
+use std::fs;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::error::Error;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(PartialEq, Debug, Clone, Default)]
 pub struct Food {
     pub name: String,
-    pub weight_grams: f32,
-    pub nutrients: HashMap<String, f32>,
+    pub brand: Option<String>,
+    pub barcode: Option<String>,
+    // Other names to find it by, e.g. "garbanzo" for chickpeas.
+    pub aliases: Vec<String>,
+    pub serving_label: Option<String>,
+    pub serving_grams: Option<f32>,
+    // Household measures such as "slice" or "cup", in the order given.
+    pub measures: Vec<Measure>,
+    // Amounts per BASIS_GRAMS of the food. Every registry nutrient has an
+    // entry; `None` means the source didn't report it, which is not the same
+    // as a measured zero. Values stored under attr_ids the registry doesn't
+    // know yet stay in the database only.
+    pub nutrients: HashMap<NutrientKind, Option<f32>>,
+}
+
+// What every stored food's nutrient amounts are for, so foods compare
+// directly and a portion is a single multiplication away.
+pub const BASIS_GRAMS: f32 = 100.0;
+
+impl Food {
+    pub fn nutrient(&self, kind: NutrientKind) -> Option<f32> {
+        self.nutrients.get(&kind).copied().flatten()
+    }
+
+    // Amounts in `grams` of the food.
+    pub fn scaled_to(&self, grams: f32) -> HashMap<NutrientKind, Option<f32>> {
+        self.nutrients
+            .iter()
+            .map(|(kind, value)| (*kind, value.map(|amount| amount * grams / BASIS_GRAMS)))
+            .collect()
+    }
+
+    // Takes whatever details and nutrients this food lacks from `other`.
+    pub fn merge(&mut self, other: &ProviderFood) {
+        self.brand = self.brand.take().or_else(|| other.brand.clone());
+        self.barcode = self.barcode.take().or_else(|| other.barcode.as_deref().and_then(normalize_barcode));
+        self.serving_label = self.serving_label.take().or_else(|| other.serving_label.clone());
+        self.serving_grams = self.serving_grams.or_else(|| typical_serving_grams(other));
+        for measure in &other.measures {
+            if !self.measures.iter().any(|stored| stored.name == measure.name) {
+                self.measures.push(measure.clone());
+            }
+        }
+
+        if let Some(scale) = basis_scale(other) {
+            for (kind, value) in &other.nutrients {
+                let stored = self.nutrients.entry(*kind).or_insert(None);
+                if stored.is_none() {
+                    *stored = Some(value * scale);
+                }
+            }
+        }
+    }
+}
+
+// Converts a provider's amounts to amounts per BASIS_GRAMS. Amounts for no
+// weight at all can't be converted.
+fn basis_scale(food: &ProviderFood) -> Option<f32> {
+    (food.serving_weight_grams > 0.0).then(|| BASIS_GRAMS / food.serving_weight_grams)
+}
+
+// Sources that report per serving give the serving as the weight their
+// amounts are for, even when it happens to be 100 g; the others name one
+// separately.
+fn typical_serving_grams(food: &ProviderFood) -> Option<f32> {
+    food.serving_grams.or_else(|| match food.basis {
+        AmountBasis::PerServing => Some(food.serving_weight_grams).filter(|grams| *grams > 0.0),
+        AmountBasis::Per100Grams => None,
+    })
+}
+
+fn unreported_nutrients() -> HashMap<NutrientKind, Option<f32>> {
+    NutrientKind::all().map(|kind| (kind, None)).collect()
+}
+
+impl From<&ProviderFood> for Food {
+    fn from(food: &ProviderFood) -> Food {
+        let mut nutrients = unreported_nutrients();
+
+        if let Some(scale) = basis_scale(food) {
+            for (kind, value) in &food.nutrients {
+                nutrients.insert(*kind, Some(value * scale));
+            }
+        }
+
+        Food {
+            name: food.name.clone(),
+            brand: food.brand.clone(),
+            barcode: food.barcode.as_deref().and_then(normalize_barcode),
+            aliases: Vec::new(),
+            serving_label: food.serving_label.clone(),
+            serving_grams: typical_serving_grams(food),
+            measures: food.measures.clone(),
+            nutrients,
+        }
+    }
+}
+
+// Nutrients missing from the registry keep their values under a synthetic key.
+pub fn nutrient_key_for_attr(attr_id: u32) -> String {
+    match NutrientKind::from_attr_id(attr_id) {
+        Some(kind) => kind.key().to_string(),
+        None => format!("attr_{}", attr_id),
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -20,183 +130,898 @@ pub struct Recipe {
     pub weights: Vec<f32>,
 }
 
-const KEYS: [&str; 99] = [
-    "calcium_ca", "carbohydrates", "cholesterol", "energy", "fatty_acids_saturated",
-    "total_lipid_fat", "fatty_acids_trans", "iron_fe", "fiber_dietary", "potassium_k",
-    "sodium_na", "protein", "sugars_total", "sugars_added", "vitamin_d", "alanine",
-    "alcohol_ethyl", "arginine", "ash", "aspartic_acid", "betaine", "caffeine",
-    "campesterol", "carotene_alpha", "carotene_beta", "vitamin_d3",
-    "choline_total", "cryptoxanthin_beta", "copper_cu", "cystine", "energy_kj",
-    "vitamin_d2", "fatty_acids_monounsaturated", "fatty_acids_polyunsaturated",
-    "fatty_acids_transmonoenoic", "fatty_acids_transpolyenoic", "fluoride_f", "folate_total",
-    "folic_acid", "folate_dfe", "folate_food", "fructose", "galactose", "glutamic_acid",
-    "glucose_dextrose", "glycine", "histidine", "hydroxyproline", "isoleucine", "lactose",
-    "leucine", "lutein_zeaxanthin", "lycopene", "lysine", "maltose", "methionine",
-    "magnesium_mg", "menaquinone", "manganese_mn", "niacin", "vitamin_e_added", "vitamin_b_added",
-    "adjusted_protein", "phosphorus_p", "pantothenic_acid", "phenylalanine", "phytosterols",
-    "proline", "retinol", "riboflavin", "selenium_se", "serine", "betasitosterol", "starch",
-    "stigmasterol", "sucrose", "theobromine", "thiamin", "threonine", "vitamin_e_alphatocopherol",
-    "tocopherol_beta", "tocopherol_delta", "tocopherol_gamma", "tryptophan", "tyrosine", "valine",
-    "vitamin_a_iu", "vitamin_a_rae", "vitamin_b12", "vitamin_b6", "vitamin_c_total_ascorbic_acid",
-    "vitamin_k_phylloquinone", "dihydrophylloquinone", "water", "zinc_zn",
-    "tocotrienol_alpha", "tocotrienol_beta", "tocotrienol_gamma", "tocotrienol_delta"
-];
-
-const SQL_INSERT: &str = "INSERT INTO food_items (name, weight_grams, calcium_ca, carbohydrates, 
-cholesterol, energy, fatty_acids_saturated, total_lipid_fat, fatty_acids_trans, iron_fe,
-fiber_dietary, potassium_k, sodium_na, protein, sugars_total, sugars_added, vitamin_d, alanine,
-alcohol_ethyl, arginine, ash, aspartic_acid, betaine, caffeine, campesterol, carotene_alpha, 
-carotene_beta, vitamin_d3, choline_total, cryptoxanthin_beta, copper_cu, cystine, 
-energy_kj, vitamin_d2, fatty_acids_monounsaturated, fatty_acids_polyunsaturated, 
-fatty_acids_transmonoenoic, fatty_acids_transpolyenoic, fluoride_f, folate_total, folic_acid, 
-folate_dfe, folate_food, fructose, galactose, glutamic_acid, glucose_dextrose, glycine, 
-histidine, hydroxyproline, isoleucine, lactose, leucine, lutein_zeaxanthin, lycopene, lysine, 
-maltose, methionine, magnesium_mg, menaquinone, manganese_mn, niacin, vitamin_e_added, 
-vitamin_b_added, adjusted_protein, phosphorus_p, pantothenic_acid, phenylalanine, 
-phytosterols, proline, retinol, riboflavin, selenium_se, serine, betasitosterol, starch, 
-stigmasterol, sucrose, theobromine, thiamin, threonine, vitamin_e_alphatocopherol, 
-tocopherol_beta, tocopherol_delta, tocopherol_gamma, tryptophan, tyrosine, valine, 
-vitamin_a_iu, vitamin_a_rae, vitamin_b12, vitamin_b6, vitamin_c_total_ascorbic_acid, 
-vitamin_k_phylloquinone, dihydrophylloquinone, water, zinc_zn, tocotrienol_alpha, 
-tocotrienol_beta, tocotrienol_gamma, tocotrienol_delta) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 
-?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, 
-?28, ?29, ?30, ?31, ?32, ?33, ?34, ?35, ?36, ?37, ?38, ?39, ?40, ?41, ?42, ?43, ?44, ?45, ?46, 
-?47, ?48, ?49, ?50, ?51, ?52, ?53, ?54, ?55, ?56, ?57, ?58, ?59, ?60, ?61, ?62, ?63, ?64, ?65, 
-?66, ?67, ?68, ?69, ?70, ?71, ?72, ?73, ?74, ?75, ?76, ?77, ?78, ?79, ?80, ?81, ?82, ?83, ?84, 
-?85, ?86, ?87, ?88, ?89, ?90, ?91, ?92, ?93, ?94, ?95, ?96, ?97, ?98, ?99, ?100, ?101)";
-
-pub fn get_foods() -> Result<Vec<Food>> {
-    let conn = Connection::open("my_database.db")?;
-    let mut foods = Vec::new();
+impl Recipe {
+    pub fn totals(&self) -> BTreeMap<NutrientKind, NutrientTotal> {
+        total_nutrients(self.foods.iter().zip(self.weights.iter().copied()))
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct PantryItem {
+    pub food: Food,
+    pub weight_grams: f32,
+    pub weight_grams_remaining: f32,
+}
+
+impl PantryItem {
+    pub fn remaining_nutrients(&self) -> HashMap<NutrientKind, Option<f32>> {
+        self.food.scaled_to(self.weight_grams_remaining)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Entry {
+    pub timestamp: NaiveDateTime,
+    pub foods: Vec<Food>,
+    pub weights: Vec<f32>,
+}
 
-    {
-        let mut stmt = conn.prepare("
-            SELECT name, weight_grams, calcium_ca, carbohydrates, 
-                cholesterol, energy, fatty_acids_saturated, total_lipid_fat, fatty_acids_trans, iron_fe,
-                fiber_dietary, potassium_k, sodium_na, protein, sugars_total, sugars_added, vitamin_d, alanine,
-                alcohol_ethyl, arginine, ash, aspartic_acid, betaine, caffeine, campesterol, carotene_alpha, 
-                carotene_beta, vitamin_d3, choline_total, cryptoxanthin_beta, copper_cu, cystine, 
-                energy_kj, vitamin_d2, fatty_acids_monounsaturated, fatty_acids_polyunsaturated, 
-                fatty_acids_transmonoenoic, fatty_acids_transpolyenoic, fluoride_f, folate_total, folic_acid, 
-                folate_dfe, folate_food, fructose, galactose, glutamic_acid, glucose_dextrose, glycine, 
-                histidine, hydroxyproline, isoleucine, lactose, leucine, lutein_zeaxanthin, lycopene, lysine, 
-                maltose, methionine, magnesium_mg, menaquinone, manganese_mn, niacin, vitamin_e_added, 
-                vitamin_b_added, adjusted_protein, phosphorus_p, pantothenic_acid, phenylalanine, 
-                phytosterols, proline, retinol, riboflavin, selenium_se, serine, betasitosterol, starch, 
-                stigmasterol, sucrose, theobromine, thiamin, threonine, vitamin_e_alphatocopherol, 
-                tocopherol_beta, tocopherol_delta, tocopherol_gamma, tryptophan, tyrosine, valine, 
-                vitamin_a_iu, vitamin_a_rae, vitamin_b12, vitamin_b6, vitamin_c_total_ascorbic_acid, 
-                vitamin_k_phylloquinone, dihydrophylloquinone, water, zinc_zn, tocotrienol_alpha, 
-                tocotrienol_beta, tocotrienol_gamma, tocotrienol_delta
-            FROM food_items
-        ")?;
-    
-        let food_iter = stmt.query_map([], |row| {
-            let name: String = row.get(0)?;
-            let weight_grams: f32 = row.get(1)?;
-
-            let mut nutrients = HashMap::new();
-
-            let nutrient_names = [
-                "calcium_ca", "carbohydrates", "cholesterol", "energy", 
-                "fatty_acids_saturated", "total_lipid_fat", "fatty_acids_trans", 
-                "iron_fe", "fiber_dietary", "potassium_k", "sodium_na", "protein", 
-                "sugars_total", "sugars_added", "vitamin_d", "alanine", 
-                "alcohol_ethyl", "arginine", "ash", "aspartic_acid", "betaine", 
-                "caffeine", "campesterol", "carotene_alpha", "carotene_beta", 
-                "vitamin_d3", "choline_total", "cryptoxanthin_beta", "copper_cu", 
-                "cystine", "energy_kj", "vitamin_d2", "fatty_acids_monounsaturated", 
-                "fatty_acids_polyunsaturated", "fatty_acids_transmonoenoic", 
-                "fatty_acids_transpolyenoic", "fluoride_f", "folate_total", 
-                "folic_acid", "folate_dfe", "folate_food", "fructose", "galactose", 
-                "glutamic_acid", "glucose_dextrose", "glycine", "histidine", 
-                "hydroxyproline", "isoleucine", "lactose", "leucine", 
-                "lutein_zeaxanthin", "lycopene", "lysine", "maltose", "methionine", 
-                "magnesium_mg", "menaquinone", "manganese_mn", "niacin", 
-                "vitamin_e_added", "vitamin_b_added", "adjusted_protein", 
-                "phosphorus_p", "pantothenic_acid", "phenylalanine", "phytosterols", 
-                "proline", "retinol", "riboflavin", "selenium_se", "serine", 
-                "betasitosterol", "starch", "stigmasterol", "sucrose", 
-                "theobromine", "thiamin", "threonine", "vitamin_e_alphatocopherol", 
-                "tocopherol_beta", "tocopherol_delta", "tocopherol_gamma", 
-                "tryptophan", "tyrosine", "valine", "vitamin_a_iu", 
-                "vitamin_a_rae", "vitamin_b12", "vitamin_b6", 
-                "vitamin_c_total_ascorbic_acid", "vitamin_k_phylloquinone", 
-                "dihydrophylloquinone", "water", "zinc_zn", "tocotrienol_alpha", 
-                "tocotrienol_beta", "tocotrienol_gamma", "tocotrienol_delta"
-            ];
-
-            for (i, &nutrient_name) in nutrient_names.iter().enumerate() {
-                let value: f32 = row.get(i + 2)?;
-                nutrients.insert(nutrient_name.to_string(), value);
+impl Entry {
+    pub fn totals(&self) -> BTreeMap<NutrientKind, NutrientTotal> {
+        total_nutrients(self.foods.iter().zip(self.weights.iter().copied()))
+    }
+}
+
+// How entries.timestamp is written; it is UTC.
+pub const TIMESTAMP_FORMAT: &str = "%m-%d-%y %H:%M:%S";
+
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct NutrientTotal {
+    // Sum over the foods that reported this nutrient.
+    pub amount: f32,
+    pub food_count: usize,
+    pub unknown_count: usize,
+}
+
+impl NutrientTotal {
+    pub fn is_complete(&self) -> bool {
+        self.unknown_count == 0
+    }
+}
+
+// Sums nutrients over (food, grams eaten) portions.
+pub fn total_nutrients<'a>(portions: impl IntoIterator<Item = (&'a Food, f32)>) -> BTreeMap<NutrientKind, NutrientTotal> {
+    let mut totals: BTreeMap<NutrientKind, NutrientTotal> = BTreeMap::new();
+
+    for (food, grams) in portions {
+        for (kind, value) in food.scaled_to(grams) {
+            let total = totals.entry(kind).or_default();
+            total.food_count += 1;
+
+            match value {
+                Some(amount) => total.amount += amount,
+                None => total.unknown_count += 1,
             }
+        }
+    }
 
-            Ok(Food {
-                name,
-                weight_grams,
-                nutrients,
-            })
-        })?;
+    totals
+}
+
+pub fn get_foods(conn: &Connection) -> Result<Vec<Food>> {
+    let foods = load_foods(conn)?.into_iter().map(|(_, food)| food).collect();
+    Ok(foods)
+}
+
+fn load_foods(conn: &Connection) -> Result<Vec<(i64, Food)>> {
+    load_foods_matching(conn, None)
+}
+
+// Every food, or just the one with `only` as its id.
+fn load_foods_matching(conn: &Connection, only: Option<i64>) -> Result<Vec<(i64, Food)>> {
+    let mut foods = Vec::new();
+    let mut index_by_id: HashMap<i64, usize> = HashMap::new();
 
-        for food in food_iter {
-            foods.push(food?);
+    let mut stmt = conn.prepare(
+        "SELECT id, name, brand, barcode, serving_label, serving_grams FROM food_items
+         WHERE ?1 IS NULL OR id = ?1",
+    )?;
+
+    let food_iter = stmt.query_map(params![only], |row| {
+        let id: i64 = row.get(0)?;
+        let food = Food {
+            name: row.get(1)?,
+            brand: row.get(2)?,
+            barcode: row.get(3)?,
+            aliases: Vec::new(),
+            serving_label: row.get(4)?,
+            serving_grams: row.get(5)?,
+            measures: Vec::new(),
+            nutrients: unreported_nutrients(),
+        };
+        Ok((id, food))
+    })?;
+
+    for row in food_iter {
+        let (id, food) = row?;
+        index_by_id.insert(id, foods.len());
+        foods.push((id, food));
+    }
+
+    let mut stmt = conn.prepare("
+        SELECT fn.food_id, n.key, fn.amount
+        FROM food_nutrients fn
+        JOIN nutrients n ON n.id = fn.nutrient_id
+        WHERE ?1 IS NULL OR fn.food_id = ?1
+    ")?;
+
+    let nutrient_iter = stmt.query_map(params![only], |row| {
+        let food_id: i64 = row.get(0)?;
+        let key: String = row.get(1)?;
+        let amount: f32 = row.get(2)?;
+        Ok((food_id, key, amount))
+    })?;
+
+    for row in nutrient_iter {
+        let (food_id, key, amount) = row?;
+        if let (Some(&index), Some(kind)) = (index_by_id.get(&food_id), NutrientKind::from_key(&key)) {
+            foods[index].1.nutrients.insert(kind, Some(amount));
+        }
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT food_id, name, grams FROM food_measures
+         WHERE ?1 IS NULL OR food_id = ?1
+         ORDER BY rowid",
+    )?;
+
+    let measure_iter = stmt.query_map(params![only], |row| {
+        let food_id: i64 = row.get(0)?;
+        Ok((food_id, Measure { name: row.get(1)?, grams: row.get(2)? }))
+    })?;
+
+    for row in measure_iter {
+        let (food_id, measure) = row?;
+        if let Some(&index) = index_by_id.get(&food_id) {
+            foods[index].1.measures.push(measure);
         }
     }
 
-    let _ = conn.close();
+    let mut stmt = conn.prepare(
+        "SELECT food_id, alias FROM food_aliases
+         WHERE ?1 IS NULL OR food_id = ?1
+         ORDER BY rowid",
+    )?;
+
+    let alias_iter = stmt.query_map(params![only], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+
+    for row in alias_iter {
+        let (food_id, alias) = row?;
+        if let Some(&index) = index_by_id.get(&food_id) {
+            foods[index].1.aliases.push(alias);
+        }
+    }
 
     Ok(foods)
 }
 
-pub fn add_food_items(foods: Vec<ApiFood>) -> Result<(), Box<dyn Error>> {
-    let nutrient_name_map = get_nutrient_name_map();
-    let conn = Connection::open("my_database.db")?;
+pub fn get_daily_totals(conn: &Connection, date: NaiveDate) -> Result<BTreeMap<NutrientKind, NutrientTotal>> {
+    let foods: HashMap<i64, Food> = load_foods(conn)?.into_iter().collect();
+
+    // Matches every TIMESTAMP_FORMAT value on that day.
+    let day_pattern = format!("{} %", date.format("%m-%d-%y"));
+
+    let mut stmt = conn.prepare("
+        SELECT ef.food_id, ef.weight_grams
+        FROM entry_foods ef
+        JOIN entries e ON e.id = ef.entry_id
+        WHERE e.timestamp LIKE ?1
+    ")?;
+
+    let portions = stmt
+        .query_map(params![day_pattern], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, f32>(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
+
+    let totals = total_nutrients(
+        portions
+            .iter()
+            .filter_map(|(food_id, grams)| foods.get(food_id).map(|food| (food, *grams))),
+    );
+
+    Ok(totals)
+}
+
+// Adds `foods` in one transaction, so a failure leaves none of them behind.
+// Names already taken are handled according to `on_conflict`.
+pub fn add_food_items(
+    conn: &mut Connection,
+    foods: &[ProviderFood],
+    on_conflict: ConflictStrategy,
+) -> Result<Vec<AddOutcome>> {
+    let tx = conn.transaction()?;
+    let mut outcomes = Vec::with_capacity(foods.len());
 
     for food in foods {
-        let mut nutrients: HashMap<&str, f32> = HashMap::new();
+        let outcome = match (find_food_id(&tx, &food.name)?, on_conflict) {
+            (None, _) => {
+                insert_food_item(&tx, food)?;
+                AddOutcome::Added
+            }
+            (Some(_), ConflictStrategy::Skip) => AddOutcome::Skipped,
+            (Some(food_id), ConflictStrategy::Overwrite) => {
+                overwrite_food_item(&tx, food_id, food)?;
+                AddOutcome::Overwritten
+            }
+            (Some(food_id), ConflictStrategy::Merge) => {
+                merge_food_item(&tx, food_id, food)?;
+                AddOutcome::Merged
+            }
+            (Some(_), ConflictStrategy::KeepBoth) => {
+                let name = unused_food_name(&tx, &food.name)?;
+                insert_food_item(&tx, &ProviderFood { name: name.clone(), ..food.clone() })?;
+                AddOutcome::Renamed(name)
+            }
+        };
+        outcomes.push(outcome);
+    }
+
+    tx.commit()?;
+    Ok(outcomes)
+}
+
+// The first of "name (2)", "name (3)"... that isn't taken.
+fn unused_food_name(conn: &Connection, name: &str) -> Result<String> {
+    for number in 2.. {
+        let candidate = format!("{} ({})", name, number);
+        if find_food_id(conn, &candidate)?.is_none() {
+            return Ok(candidate);
+        }
+    }
+    unreachable!("ran out of numbers for {}", name)
+}
+
+fn overwrite_food_item(conn: &Connection, food_id: i64, food: &ProviderFood) -> Result<()> {
+    conn.execute(
+        "UPDATE food_items
+         SET brand = ?2, barcode = ?3, serving_label = ?4, serving_grams = ?5
+         WHERE id = ?1",
+        params![
+            food_id,
+            food.brand,
+            food.barcode.as_deref().and_then(normalize_barcode),
+            food.serving_label,
+            typical_serving_grams(food),
+        ],
+    )?;
+    conn.execute("DELETE FROM food_nutrients WHERE food_id = ?1", params![food_id])?;
+    conn.execute("DELETE FROM food_measures WHERE food_id = ?1", params![food_id])?;
+
+    insert_food_nutrients(conn, food_id, food, false)?;
+    insert_food_measures(conn, food_id, &food.measures, false)
+}
+
+// Keeps every value already stored; only gaps are filled from `food`.
+fn merge_food_item(conn: &Connection, food_id: i64, food: &ProviderFood) -> Result<()> {
+    conn.execute(
+        "UPDATE food_items
+         SET brand = COALESCE(brand, ?2),
+             barcode = COALESCE(barcode, ?3),
+             serving_label = COALESCE(serving_label, ?4),
+             serving_grams = COALESCE(serving_grams, ?5)
+         WHERE id = ?1",
+        params![
+            food_id,
+            food.brand,
+            food.barcode.as_deref().and_then(normalize_barcode),
+            food.serving_label,
+            typical_serving_grams(food),
+        ],
+    )?;
+
+    insert_food_nutrients(conn, food_id, food, true)?;
+    insert_food_measures(conn, food_id, &food.measures, true)
+}
+
+fn insert_food_item(conn: &Connection, food: &ProviderFood) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO food_items (name, brand, barcode, serving_label, serving_grams)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            food.name,
+            food.brand,
+            food.barcode.as_deref().and_then(normalize_barcode),
+            food.serving_label,
+            typical_serving_grams(food),
+        ],
+    )?;
+    let food_id = conn.last_insert_rowid();
+
+    insert_food_nutrients(conn, food_id, food, false)?;
+    insert_food_measures(conn, food_id, &food.measures, false)?;
+    Ok(food_id)
+}
+
+// Stores `food`'s nutrients per BASIS_GRAMS. With `keep_existing`, a
+// nutrient the food already has a value for is left alone.
+fn insert_food_nutrients(conn: &Connection, food_id: i64, food: &ProviderFood, keep_existing: bool) -> Result<()> {
+    let scale = match basis_scale(food) {
+        Some(scale) => scale,
+        None => return Ok(()),
+    };
+    let sql = if keep_existing {
+        "INSERT OR IGNORE INTO food_nutrients (food_id, nutrient_id, amount) VALUES (?1, ?2, ?3)"
+    } else {
+        "INSERT OR REPLACE INTO food_nutrients (food_id, nutrient_id, amount) VALUES (?1, ?2, ?3)"
+    };
+    let reported = food
+        .nutrients
+        .iter()
+        .map(|(kind, value)| (kind.attr_id(), *value))
+        .chain(food.other_nutrients.iter().map(|(attr_id, value)| (*attr_id, *value)));
+
+    for (attr_id, value) in reported {
+        let nutrient_id = nutrient_id_for_attr(conn, attr_id)?;
+        conn.execute(sql, params![food_id, nutrient_id, value * scale])?;
+    }
 
-        for nutrient in &food.full_nutrients {
-            if let Some(&nutrient_name) = nutrient_name_map.get(&nutrient.attr_id) {
-                nutrients.insert(nutrient_name, nutrient.value);
+    Ok(())
+}
+
+// With `keep_existing`, a measure the food already has is left alone.
+fn insert_food_measures(conn: &Connection, food_id: i64, measures: &[Measure], keep_existing: bool) -> Result<()> {
+    let sql = if keep_existing {
+        "INSERT OR IGNORE INTO food_measures (food_id, name, grams) VALUES (?1, ?2, ?3)"
+    } else {
+        "INSERT OR REPLACE INTO food_measures (food_id, name, grams) VALUES (?1, ?2, ?3)"
+    };
+
+    for measure in measures {
+        conn.execute(sql, params![food_id, measure.name, measure.grams])?;
+    }
+    Ok(())
+}
+
+// Looks up the dictionary row for a USDA attr_id, registering a placeholder
+// for ids the dictionary doesn't know yet so their values are still kept.
+fn nutrient_id_for_attr(conn: &Connection, attr_id: u32) -> Result<i64> {
+    let existing = conn.query_row(
+        "SELECT id FROM nutrients WHERE attr_id = ?1",
+        params![attr_id],
+        |row| row.get(0),
+    );
+
+    match existing {
+        Ok(id) => Ok(id),
+        Err(RusqliteError::QueryReturnedNoRows) => {
+            conn.execute(
+                "INSERT INTO nutrients (key, name, unit, attr_id) VALUES (?1, ?2, NULL, ?3)",
+                params![nutrient_key_for_attr(attr_id), format!("Nutrient {}", attr_id), attr_id],
+            )?;
+            Ok(conn.last_insert_rowid())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub fn find_food_id(conn: &Connection, name: &str) -> Result<Option<i64>> {
+    let found = conn.query_row(
+        "SELECT id FROM food_items WHERE name = ?1",
+        params![name],
+        |row| row.get(0),
+    );
+
+    match found {
+        Ok(id) => Ok(Some(id)),
+        Err(RusqliteError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn find_food(conn: &Connection, name: &str) -> Result<Option<Food>> {
+    Ok(find_food_with_id(conn, name)?.map(|(_, food)| food))
+}
+
+fn find_food_with_id(conn: &Connection, name: &str) -> Result<Option<(i64, Food)>> {
+    match find_food_id(conn, name)? {
+        Some(id) => Ok(load_foods_matching(conn, Some(id))?.pop()),
+        None => Ok(None),
+    }
+}
+
+// Rewrites a food's details, aliases, measures and registry nutrients. Values stored under
+// attr_ids the registry doesn't know are left as they are.
+pub fn update_food_item(conn: &mut Connection, food_id: i64, food: &Food) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "UPDATE food_items
+         SET name = ?2, brand = ?3, barcode = ?4, serving_label = ?5, serving_grams = ?6
+         WHERE id = ?1",
+        params![
+            food_id,
+            food.name,
+            food.brand,
+            food.barcode.as_deref().and_then(normalize_barcode),
+            food.serving_label,
+            food.serving_grams,
+        ],
+    )?;
+
+    for (kind, value) in &food.nutrients {
+        let nutrient_id = nutrient_id_for_attr(&tx, kind.attr_id())?;
+        match value {
+            Some(amount) => tx.execute(
+                "INSERT OR REPLACE INTO food_nutrients (food_id, nutrient_id, amount) VALUES (?1, ?2, ?3)",
+                params![food_id, nutrient_id, amount],
+            )?,
+            None => tx.execute(
+                "DELETE FROM food_nutrients WHERE food_id = ?1 AND nutrient_id = ?2",
+                params![food_id, nutrient_id],
+            )?,
+        };
+    }
+
+    // The edited lists are the whole of them, so removed measures and
+    // aliases go too.
+    tx.execute("DELETE FROM food_measures WHERE food_id = ?1", params![food_id])?;
+    insert_food_measures(&tx, food_id, &food.measures, false)?;
+    tx.execute("DELETE FROM food_aliases WHERE food_id = ?1", params![food_id])?;
+    for alias in &food.aliases {
+        tx.execute(
+            "INSERT OR IGNORE INTO food_aliases (food_id, alias) VALUES (?1, ?2)",
+            params![food_id, alias],
+        )?;
+    }
+
+    tx.commit()
+}
+
+// How many pantry items, recipes and entries use the food.
+pub fn count_food_references(conn: &Connection, food_id: i64) -> Result<(usize, usize, usize)> {
+    let count = |sql: &str| conn.query_row(sql, params![food_id], |row| row.get::<_, i64>(0)).map(|count| count as usize);
+
+    Ok((
+        count("SELECT COUNT(*) FROM pantry WHERE food_id = ?1")?,
+        count("SELECT COUNT(DISTINCT recipe_id) FROM recipe_ingredients WHERE food_id = ?1")?,
+        count("SELECT COUNT(DISTINCT entry_id) FROM entry_foods WHERE food_id = ?1")?,
+    ))
+}
+
+// Deletes a food along with its nutrients. Whatever used it is moved to
+// `replacement` if there is one, and otherwise deleted too.
+pub fn delete_food_item(conn: &mut Connection, food_id: i64, replacement: Option<i64>) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    match replacement {
+        Some(replacement_id) => {
+            tx.execute(
+                "UPDATE pantry SET food_id = ?2 WHERE food_id = ?1",
+                params![food_id, replacement_id],
+            )?;
+            reassign_portions(&tx, "recipe_ingredients", "recipe_id", food_id, replacement_id)?;
+            reassign_portions(&tx, "entry_foods", "entry_id", food_id, replacement_id)?;
+        }
+        None => {
+            for table in ["pantry", "recipe_ingredients", "entry_foods"] {
+                tx.execute(&format!("DELETE FROM {} WHERE food_id = ?1", table), params![food_id])?;
             }
         }
+    }
+
+    tx.execute("DELETE FROM food_nutrients WHERE food_id = ?1", params![food_id])?;
+    tx.execute("DELETE FROM food_measures WHERE food_id = ?1", params![food_id])?;
+    tx.execute("DELETE FROM food_aliases WHERE food_id = ?1", params![food_id])?;
+    tx.execute("DELETE FROM food_items WHERE id = ?1", params![food_id])?;
+    tx.commit()
+}
+
+// Where a recipe or entry already has the replacement, the deleted food's
+// row is merged into the replacement's: its weight is added there and the
+// row removed. Everywhere else the row is simply pointed at the replacement.
+fn reassign_portions(conn: &Connection, table: &str, owner: &str, food_id: i64, replacement_id: i64) -> Result<()> {
+    conn.execute(
+        &format!(
+            "UPDATE {table} SET weight_grams = weight_grams + (
+                 SELECT SUM(old.weight_grams) FROM {table} old
+                 WHERE old.{owner} = {table}.{owner} AND old.food_id = ?1
+             )
+             WHERE food_id = ?2 AND {owner} IN (SELECT {owner} FROM {table} WHERE food_id = ?1)",
+            table = table,
+            owner = owner,
+        ),
+        params![food_id, replacement_id],
+    )?;
+    conn.execute(
+        &format!(
+            "DELETE FROM {table} WHERE food_id = ?1 AND {owner} IN (SELECT {owner} FROM {table} WHERE food_id = ?2)",
+            table = table,
+            owner = owner,
+        ),
+        params![food_id, replacement_id],
+    )?;
+    conn.execute(
+        &format!("UPDATE {} SET food_id = ?2 WHERE food_id = ?1", table),
+        params![food_id, replacement_id],
+    )?;
+    Ok(())
+}
+
+// How many index matches are ranked for each result wanted. The index orders
+// by bm25, which knows nothing of typos, so it needs some room.
+const SEARCH_CANDIDATES_PER_RESULT: usize = 5;
+
+// Foods whose name, brand or aliases match `query`, best first. The FTS5
+// index finds the candidates, with each query word looked up as a prefix
+// and as any indexed word it could be a typo of; food_search ranks them.
+pub fn search_foods(conn: &Connection, query: &str, limit: usize) -> Result<Vec<Food>> {
+    let words = food_search::words(query);
+    if words.is_empty() || limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut clauses = Vec::with_capacity(words.len());
+    for word in &words {
+        let mut options = vec![format!("\"{}\"*", word)];
+        for term in similar_terms(conn, word)? {
+            options.push(format!("\"{}\"", term));
+        }
+        clauses.push(format!("({})", options.join(" OR ")));
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT rowid FROM food_search WHERE food_search MATCH ?1
+         ORDER BY bm25(food_search, 10.0, 1.0, 5.0)
+         LIMIT ?2",
+    )?;
+    let ids = stmt
+        .query_map(
+            params![clauses.join(" AND "), (limit * SEARCH_CANDIDATES_PER_RESULT) as i64],
+            |row| row.get::<_, i64>(0),
+        )?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut candidates = Vec::with_capacity(ids.len());
+    for id in ids {
+        candidates.extend(load_foods_matching(conn, Some(id))?.into_iter().map(|(_, food)| food));
+    }
+
+    Ok(food_search::rank(candidates, query, limit))
+}
+
+// Indexed words `word` could be a misspelling of. Typos in the first letter
+// are rare enough not to be worth scanning the whole vocabulary for.
+fn similar_terms(conn: &Connection, word: &str) -> Result<Vec<String>> {
+    let first = match word.chars().next() {
+        Some(first) => first,
+        None => return Ok(Vec::new()),
+    };
+    let after = char::from_u32(first as u32 + 1).unwrap_or(char::MAX);
+
+    let mut stmt = conn.prepare("SELECT term FROM food_search_terms WHERE term >= ?1 AND term < ?2")?;
+    let terms = stmt
+        .query_map(params![first.to_string(), after.to_string()], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(terms
+        .into_iter()
+        .filter(|term| !term.starts_with(word) && food_search::is_candidate(word, term))
+        .collect())
+}
+
+// Barcodes are stored normalized, so `barcode` should be too.
+pub fn find_food_by_barcode(conn: &Connection, barcode: &str) -> Result<Option<Food>> {
+    let found = conn.query_row(
+        "SELECT id FROM food_items WHERE barcode = ?1",
+        params![barcode],
+        |row| row.get(0),
+    );
+
+    match found {
+        Ok(id) => Ok(load_foods_matching(conn, Some(id))?.pop().map(|(_, food)| food)),
+        Err(RusqliteError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn get_pantry(conn: &Connection) -> Result<Vec<PantryItem>> {
+    let foods: HashMap<i64, Food> = load_foods(conn)?.into_iter().collect();
+
+    let mut stmt = conn.prepare("SELECT food_id, weight_grams, weight_grams_remaining FROM pantry ORDER BY id")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, f32>(1)?, row.get::<_, f32>(2)?)))?
+        .collect::<Result<Vec<_>>>()?;
+
+    let pantry = rows
+        .into_iter()
+        .filter_map(|(food_id, weight_grams, weight_grams_remaining)| {
+            foods.get(&food_id).map(|food| PantryItem {
+                food: food.clone(),
+                weight_grams,
+                weight_grams_remaining,
+            })
+        })
+        .collect();
+
+    Ok(pantry)
+}
+
+pub fn insert_pantry_item(conn: &Connection, food_id: i64, weight_grams: f32) -> Result<()> {
+    conn.execute(
+        "INSERT INTO pantry (food_id, weight_grams, weight_grams_remaining) VALUES (?1, ?2, ?2)",
+        params![food_id, weight_grams],
+    )?;
+    Ok(())
+}
+
+pub fn get_recipes(conn: &Connection) -> Result<Vec<Recipe>> {
+    let foods: HashMap<i64, Food> = load_foods(conn)?.into_iter().collect();
+    let mut recipes: Vec<Recipe> = Vec::new();
+    let mut index_by_id: HashMap<i64, usize> = HashMap::new();
+
+    let mut stmt = conn.prepare("SELECT id, name FROM recipes ORDER BY name")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
 
-        match insert_food_item(&conn, &food, &nutrients) {
-            Ok(_) => {},
-            Err(e) => return Err(Box::new(e))
+    for (id, name) in rows {
+        index_by_id.insert(id, recipes.len());
+        recipes.push(Recipe { name, foods: Vec::new(), weights: Vec::new() });
+    }
+
+    let mut stmt = conn.prepare("SELECT recipe_id, food_id, weight_grams FROM recipe_ingredients ORDER BY id")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, f32>(2)?)))?
+        .collect::<Result<Vec<_>>>()?;
+
+    for (recipe_id, food_id, weight_grams) in rows {
+        if let (Some(&index), Some(food)) = (index_by_id.get(&recipe_id), foods.get(&food_id)) {
+            recipes[index].foods.push(food.clone());
+            recipes[index].weights.push(weight_grams);
         }
     }
 
-    let _ = conn.close();
+    Ok(recipes)
+}
 
+// A recipe or entry holds one portion per food, so listing a food again
+// adds to the portion it already has instead of failing.
+fn add_portion(conn: &Connection, table: &str, owner: &str, owner_id: i64, food_id: i64, weight_grams: f32) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO {table} ({owner}, food_id, weight_grams) VALUES (?1, ?2, ?3)
+             ON CONFLICT ({owner}, food_id) DO UPDATE SET weight_grams = weight_grams + excluded.weight_grams",
+            table = table,
+            owner = owner,
+        ),
+        params![owner_id, food_id, weight_grams],
+    )?;
     Ok(())
 }
 
-fn insert_food_item(
+pub fn insert_recipe(conn: &mut Connection, name: &str, ingredients: &[(i64, f32)]) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    tx.execute("INSERT INTO recipes (name) VALUES (?1)", params![name])?;
+    let recipe_id = tx.last_insert_rowid();
+
+    for (food_id, weight_grams) in ingredients {
+        add_portion(&tx, "recipe_ingredients", "recipe_id", recipe_id, *food_id, *weight_grams)?;
+    }
+
+    tx.commit()
+}
+
+pub fn get_entries(conn: &Connection) -> Result<Vec<Entry>> {
+    let foods: HashMap<i64, Food> = load_foods(conn)?.into_iter().collect();
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut index_by_id: HashMap<i64, usize> = HashMap::new();
+
+    let mut stmt = conn.prepare("SELECT id, timestamp FROM entries ORDER BY id")?;
+    let rows = stmt
+        .query_map([], |row| {
+            let id: i64 = row.get(0)?;
+            let text: String = row.get(1)?;
+            let timestamp = NaiveDateTime::parse_from_str(&text, TIMESTAMP_FORMAT).map_err(|e| {
+                RusqliteError::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
+            })?;
+            Ok((id, timestamp))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    for (id, timestamp) in rows {
+        index_by_id.insert(id, entries.len());
+        entries.push(Entry { timestamp, foods: Vec::new(), weights: Vec::new() });
+    }
+
+    let mut stmt = conn.prepare("SELECT entry_id, food_id, weight_grams FROM entry_foods ORDER BY id")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, f32>(2)?)))?
+        .collect::<Result<Vec<_>>>()?;
+
+    for (entry_id, food_id, weight_grams) in rows {
+        if let (Some(&index), Some(food)) = (index_by_id.get(&entry_id), foods.get(&food_id)) {
+            entries[index].foods.push(food.clone());
+            entries[index].weights.push(weight_grams);
+        }
+    }
+
+    Ok(entries)
+}
+
+pub fn insert_entry(conn: &mut Connection, timestamp: NaiveDateTime, foods: &[(i64, f32)]) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "INSERT INTO entries (timestamp) VALUES (?1)",
+        params![timestamp.format(TIMESTAMP_FORMAT).to_string()],
+    )?;
+    let entry_id = tx.last_insert_rowid();
+
+    for (food_id, weight_grams) in foods {
+        add_portion(&tx, "entry_foods", "entry_id", entry_id, *food_id, *weight_grams)?;
+    }
+
+    tx.commit()
+}
+
+pub fn get_cached_lookup(conn: &Connection, provider: &str, query: &str) -> Result<Option<CachedLookup>> {
+    let found = conn.query_row(
+        "SELECT response, fetched_at FROM lookup_cache WHERE provider = ?1 AND query = ?2",
+        params![provider, query],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+    );
+
+    let (response, fetched_at) = match found {
+        Ok(row) => row,
+        Err(RusqliteError::QueryReturnedNoRows) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let foods = serde_json::from_str(&response).map_err(|e| {
+        RusqliteError::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    let fetched_at = DateTime::from_timestamp(fetched_at, 0)
+        .ok_or(RusqliteError::IntegralValueOutOfRange(1, fetched_at))?
+        .naive_utc();
+
+    Ok(Some(CachedLookup { foods, fetched_at }))
+}
+
+pub fn cache_lookup(
     conn: &Connection,
-    food: &ApiFood,
-    nutrients: &HashMap<&str, f32>,
-) -> Result<usize> {
-    let params = create_params(food, nutrients);
-    conn.execute(SQL_INSERT, params.as_slice())
+    provider: &str,
+    query: &str,
+    foods: &[ProviderFood],
+    fetched_at: NaiveDateTime,
+) -> Result<()> {
+    let response = serde_json::to_string(foods).map_err(|e| RusqliteError::ToSqlConversionFailure(Box::new(e)))?;
+
+    conn.execute(
+        "INSERT INTO lookup_cache (provider, query, response, fetched_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (provider, query) DO UPDATE SET response = excluded.response, fetched_at = excluded.fetched_at",
+        params![provider, query, response, fetched_at.and_utc().timestamp()],
+    )?;
+    Ok(())
+}
+
+pub fn get_pending_lookups(conn: &Connection) -> Result<Vec<PendingLookup>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.provider, p.query, p.queued_at, e.timestamp, p.attempts, p.last_error, p.stalled
+         FROM pending_lookups p LEFT JOIN entries e ON e.id = p.entry_id
+         ORDER BY p.id",
+    )?;
+
+    let lookups = stmt
+        .query_map([], |row| {
+            let queued_at: i64 = row.get(3)?;
+            let entry_timestamp = match row.get::<_, Option<String>>(4)? {
+                Some(text) => Some(NaiveDateTime::parse_from_str(&text, TIMESTAMP_FORMAT).map_err(|e| {
+                    RusqliteError::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(e))
+                })?),
+                None => None,
+            };
+
+            Ok(PendingLookup {
+                id: row.get(0)?,
+                provider: row.get(1)?,
+                query: row.get(2)?,
+                queued_at: DateTime::from_timestamp(queued_at, 0)
+                    .ok_or(RusqliteError::IntegralValueOutOfRange(3, queued_at))?
+                    .naive_utc(),
+                entry_timestamp,
+                attempts: row.get(5)?,
+                last_error: row.get(6)?,
+                stalled: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(lookups)
+}
+
+// Returns the id of the lookup queued for `query`, which is the one already
+// waiting if the same text was queued before.
+pub fn queue_lookup(conn: &Connection, provider: &str, query: &str, queued_at: NaiveDateTime) -> Result<i64> {
+    let query = normalize_query(query);
+    conn.execute(
+        "INSERT INTO pending_lookups (provider, query, queued_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT (provider, query) DO NOTHING",
+        params![provider, query, queued_at.and_utc().timestamp()],
+    )?;
+    conn.query_row(
+        "SELECT id FROM pending_lookups WHERE provider = ?1 AND query = ?2",
+        params![provider, query],
+        |row| row.get(0),
+    )
+}
+
+// Logs an entry with no foods yet; they are added when the lookup resolves.
+// A lookup only ever has one, however often this is asked for.
+pub fn log_pending_entry(conn: &mut Connection, lookup_id: i64, timestamp: NaiveDateTime) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    let existing: Option<i64> = tx.query_row(
+        "SELECT entry_id FROM pending_lookups WHERE id = ?1",
+        params![lookup_id],
+        |row| row.get(0),
+    )?;
+    if existing.is_some() {
+        return Ok(());
+    }
+
+    tx.execute(
+        "INSERT INTO entries (timestamp) VALUES (?1)",
+        params![timestamp.format(TIMESTAMP_FORMAT).to_string()],
+    )?;
+    let entry_id = tx.last_insert_rowid();
+    tx.execute(
+        "UPDATE pending_lookups SET entry_id = ?1 WHERE id = ?2",
+        params![entry_id, lookup_id],
+    )?;
+
+    tx.commit()
 }
 
-fn create_params<'a>(food: &'a ApiFood, nutrients: &'a HashMap<&str, f32>) -> Vec<&'a dyn ToSql> {
-    let mut params: Vec<&'a dyn ToSql> = Vec::new();
-    params.push(&food.food_name);
-    params.push(&food.serving_weight_grams);
+// Stores the foods a queued query resolved to, adds them to its placeholder
+// entry at their reported serving weight, and drops it from the queue.
+pub fn resolve_lookup(conn: &mut Connection, lookup_id: i64, foods: &[ProviderFood]) -> Result<()> {
+    let tx = conn.transaction()?;
 
-    for &key in &KEYS {
-        params.push(nutrients.get(key).unwrap_or(&0.0));
+    let entry_id: Option<i64> = tx.query_row(
+        "SELECT entry_id FROM pending_lookups WHERE id = ?1",
+        params![lookup_id],
+        |row| row.get(0),
+    )?;
+
+    for food in foods {
+        let food_id = match find_food_id(&tx, &food.name)? {
+            Some(id) => id,
+            None => insert_food_item(&tx, food)?,
+        };
+
+        if let Some(entry_id) = entry_id {
+            // "1 egg and 2 eggs" resolves to two eggs, which share one portion.
+            add_portion(&tx, "entry_foods", "entry_id", entry_id, food_id, food.serving_weight_grams)?;
+        }
     }
 
-    params
+    tx.execute("DELETE FROM pending_lookups WHERE id = ?1", params![lookup_id])?;
+    tx.commit()
+}
+
+pub fn record_lookup_failure(conn: &Connection, lookup_id: i64, error: &str, stalled: bool) -> Result<()> {
+    conn.execute(
+        "UPDATE pending_lookups SET attempts = attempts + 1, last_error = ?1, stalled = ?2 WHERE id = ?3",
+        params![error, stalled, lookup_id],
+    )?;
+    Ok(())
 }
 
-pub fn add_pantry_item() -> Result<(), Box<dyn Error>> {
-    // Open or create the database file
-    let conn = Connection::open("my_database.db")?;
+// Drops a queued query along with its placeholder entry, if that is still empty.
+pub fn discard_lookup(conn: &mut Connection, lookup_id: i64) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "DELETE FROM entries WHERE id = (SELECT entry_id FROM pending_lookups WHERE id = ?1)
+         AND NOT EXISTS (SELECT 1 FROM entry_foods WHERE entry_id = entries.id)",
+        params![lookup_id],
+    )?;
+    tx.execute("DELETE FROM pending_lookups WHERE id = ?1", params![lookup_id])?;
+
+    tx.commit()
+}
 
+pub fn add_pantry_item(conn: &Connection) -> Result<(), Box<dyn Error>> {
     loop {
         let food_name = read_input("Enter food name (or type 'exit' to cancel): ");
         if food_name.to_lowercase() == "exit" {
@@ -204,27 +1029,15 @@ pub fn add_pantry_item() -> Result<(), Box<dyn Error>> {
             return Ok(());
         }
 
-        let food_id: Result<i32> = conn.query_row(
-            "SELECT id FROM food_items WHERE name = ?1",
-            params![food_name],
-            |row| row.get(0),
-        );
-
-        let food_id = match food_id {
-            Ok(id) => id,
-            Err(_) => {
-                println!("Food name not found. Please try again.");
+        let (food_id, food) = match find_food_with_id(conn, &food_name)? {
+            Some(found) => found,
+            None => {
+                println!("Food name not found.{} Please try again.", suggest_foods(conn, &food_name)?);
                 continue; // Prompt for another food name
             }
         };
 
-        let weight_grams: f32 = match read_input("Weight grams: ").trim().parse() {
-            Ok(weight) => weight,
-            Err(_) => {
-                println!("Invalid input for weight grams.");
-                return Ok(()); // Exit the function without error
-            }
-        };
+        let weight_grams = read_grams(&food);
 
         conn.execute(
             "INSERT INTO pantry (food_id, weight_grams, weight_grams_remaining) VALUES (?1, ?2, ?2)",
@@ -236,10 +1049,7 @@ pub fn add_pantry_item() -> Result<(), Box<dyn Error>> {
     }
 }
 
-pub fn add_recipe() -> Result<(), Box<dyn Error>> {
-    // Open or create the database file
-    let conn = Connection::open("my_database.db")?;
-
+pub fn add_recipe(conn: &Connection) -> Result<(), Box<dyn Error>> {
     let recipe_name = read_input("Enter recipe name: ");
 
     // Check if the recipe name already exists
@@ -259,7 +1069,7 @@ pub fn add_recipe() -> Result<(), Box<dyn Error>> {
         params![recipe_name],
     )?;
 
-    let recipe_id: i32 = conn.query_row(
+    let recipe_id: i64 = conn.query_row(
         "SELECT id FROM recipes WHERE name = ?1",
         params![recipe_name],
         |row| row.get(0),
@@ -271,30 +1081,15 @@ pub fn add_recipe() -> Result<(), Box<dyn Error>> {
             break;
         }
 
-        let food_id: Result<i32> = conn.query_row(
-            "SELECT id FROM food_items WHERE name = ?1",
-            params![food_name],
-            |row| row.get(0),
-        );
-
-        match food_id {
-            Ok(id) => {
-                let weight_grams: f32 = loop {
-                    let input = read_input("Enter weight in grams: ");
-                    match input.trim().parse() {
-                        Ok(value) => break value,
-                        Err(_) => println!("Invalid input for weight grams. Please enter a valid number."),
-                    }
-                };
-
-                conn.execute(
-                    "INSERT INTO recipe_ingredients (recipe_id, food_id, weight_grams) VALUES (?1, ?2, ?3)",
-                    params![recipe_id, id, weight_grams],
-                )?;
+        match find_food_with_id(conn, &food_name)? {
+            Some((id, food)) => {
+                let weight_grams = read_grams(&food);
+
+                add_portion(conn, "recipe_ingredients", "recipe_id", recipe_id, id, weight_grams)?;
                 println!("Ingredient added.");
             }
-            Err(_) => {
-                println!("Food item not found. Please try again.");
+            None => {
+                println!("Food item not found.{} Please try again.", suggest_foods(conn, &food_name)?);
             }
         }
     }
@@ -304,21 +1099,18 @@ pub fn add_recipe() -> Result<(), Box<dyn Error>> {
 }
 
 
-pub fn add_entry() -> Result<(), Box<dyn Error>> {
-    // Open or create the database file
-    let conn = Connection::open("my_database.db")?;
-
+pub fn add_entry(conn: &Connection) -> Result<(), Box<dyn Error>> {
     let now = Utc::now();
 
     // Format the timestamp as a string
-    let timestamp = now.format("%m-%d-%y %H:%M:%S").to_string();
+    let timestamp = now.format(TIMESTAMP_FORMAT).to_string();
 
     conn.execute(
         "INSERT INTO entries (timestamp) VALUES (?1)",
         params![timestamp],
     )?;
 
-    let entry_id: i32 = conn.query_row(
+    let entry_id: i64 = conn.query_row(
         "SELECT id FROM entries WHERE timestamp = ?1",
         params![timestamp],
         |row| row.get(0),
@@ -330,30 +1122,15 @@ pub fn add_entry() -> Result<(), Box<dyn Error>> {
             break;
         }
 
-        let food_id_result: Result<i32> = conn.query_row(
-            "SELECT id FROM food_items WHERE name = ?1",
-            params![food_name],
-            |row| row.get(0),
-        );
-
-        match food_id_result {
-            Ok(food_id) => {
-                let weight_grams: f32 = loop {
-                    let weight_input = read_input("Enter weight in grams: ");
-                    match weight_input.trim().parse() {
-                        Ok(weight) => break weight,
-                        Err(_) => println!("Please enter a valid number for weight."),
-                    }
-                };
-
-                conn.execute(
-                    "INSERT INTO entry_foods (entry_id, food_id, weight_grams) VALUES (?1, ?2, ?3)",
-                    params![entry_id, food_id, weight_grams],
-                )?;
+        match find_food_with_id(conn, &food_name)? {
+            Some((food_id, food)) => {
+                let weight_grams = read_grams(&food);
+
+                add_portion(conn, "entry_foods", "entry_id", entry_id, food_id, weight_grams)?;
                 println!("Ingredient added.");
             }
-            Err(_) => {
-                println!("Food item not found. Please try again.");
+            None => {
+                println!("Food item not found.{} Please try again.", suggest_foods(conn, &food_name)?);
             }
         }
     }
@@ -363,6 +1140,27 @@ pub fn add_entry() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+// " Did you mean ...?" for a name that didn't match exactly, if anything is close.
+fn suggest_foods(conn: &Connection, name: &str) -> Result<String> {
+    let close: Vec<String> = search_foods(conn, name, 3)?.into_iter().map(|food| food.name).collect();
+    if close.is_empty() {
+        Ok(String::new())
+    } else {
+        Ok(format!(" Did you mean {}?", close.join(", ")))
+    }
+}
+
+// Asks until the amount converts, so "2 slices" works as well as "60".
+fn read_grams(food: &Food) -> f32 {
+    loop {
+        let input = read_input("Enter amount (grams, or e.g. 2 slices or 1/2 cup): ");
+        match to_grams(food, &input) {
+            Ok(grams) => break grams,
+            Err(err) => println!("{}", err),
+        }
+    }
+}
+
 pub fn read_input(prompt: &str) -> String {
     print!("{}", prompt);
     io::stdout().flush().unwrap();
@@ -371,167 +1169,49 @@ pub fn read_input(prompt: &str) -> String {
     input.trim().to_string()
 }
 
-pub fn create_database() -> Result<(), Box<dyn Error>> {
-    // Open or create the database file
-    let conn = Connection::open("my_database.db")?;
+pub struct Database {
+    pub path: PathBuf,
+    pub conn: Connection,
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS food_items (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL UNIQUE,
-            weight_grams REAL NOT NULL,
-            calcium_ca REAL NOT NULL,
-            carbohydrates REAL NOT NULL,
-            cholesterol REAL NOT NULL,
-            energy REAL NOT NULL,
-            fatty_acids_saturated REAL NOT NULL,
-            total_lipid_fat REAL NOT NULL,
-            fatty_acids_trans REAL NOT NULL,
-            iron_fe REAL NOT NULL,
-            fiber_dietary REAL NOT NULL,
-            potassium_k REAL NOT NULL,
-            sodium_na REAL NOT NULL,
-            protein REAL NOT NULL,
-            sugars_total REAL NOT NULL,
-            sugars_added REAL NOT NULL,
-            vitamin_d REAL NOT NULL,
-            alanine REAL NOT NULL,
-            alcohol_ethyl REAL NOT NULL,
-            arginine REAL NOT NULL,
-            ash REAL NOT NULL,
-            aspartic_acid REAL NOT NULL,
-            betaine REAL NOT NULL,
-            caffeine REAL NOT NULL,
-            campesterol REAL NOT NULL,
-            carotene_alpha REAL NOT NULL,
-            carotene_beta REAL NOT NULL,
-            vitamin_d3 REAL NOT NULL,
-            choline_total REAL NOT NULL,
-            cryptoxanthin_beta REAL NOT NULL,
-            copper_cu REAL NOT NULL,
-            cystine REAL NOT NULL,
-            energy_kj REAL NOT NULL,
-            vitamin_d2 REAL NOT NULL,
-            fatty_acids_monounsaturated REAL NOT NULL,
-            fatty_acids_polyunsaturated REAL NOT NULL,
-            fatty_acids_transmonoenoic REAL NOT NULL,
-            fatty_acids_transpolyenoic REAL NOT NULL,
-            fluoride_f REAL NOT NULL,
-            folate_total REAL NOT NULL,
-            folic_acid REAL NOT NULL,
-            folate_dfe REAL NOT NULL,
-            folate_food REAL NOT NULL,
-            fructose REAL NOT NULL,
-            galactose REAL NOT NULL,
-            glutamic_acid REAL NOT NULL,
-            glucose_dextrose REAL NOT NULL,
-            glycine REAL NOT NULL,
-            histidine REAL NOT NULL,
-            hydroxyproline REAL NOT NULL,
-            isoleucine REAL NOT NULL,
-            lactose REAL NOT NULL,
-            leucine REAL NOT NULL,
-            lutein_zeaxanthin REAL NOT NULL,
-            lycopene REAL NOT NULL,
-            lysine REAL NOT NULL,
-            maltose REAL NOT NULL,
-            methionine REAL NOT NULL,
-            magnesium_mg REAL NOT NULL,
-            menaquinone REAL NOT NULL,
-            manganese_mn REAL NOT NULL,
-            niacin REAL NOT NULL,
-            vitamin_e_added REAL NOT NULL,
-            vitamin_b_added REAL NOT NULL,
-            adjusted_protein REAL NOT NULL,
-            phosphorus_p REAL NOT NULL,
-            pantothenic_acid REAL NOT NULL,
-            phenylalanine REAL NOT NULL,
-            phytosterols REAL NOT NULL,
-            proline REAL NOT NULL,
-            retinol REAL NOT NULL,
-            riboflavin REAL NOT NULL,
-            selenium_se REAL NOT NULL,
-            serine REAL NOT NULL,
-            betasitosterol REAL NOT NULL,
-            starch REAL NOT NULL,
-            stigmasterol REAL NOT NULL,
-            sucrose REAL NOT NULL,
-            theobromine REAL NOT NULL,
-            thiamin REAL NOT NULL,
-            threonine REAL NOT NULL,
-            vitamin_e_alphatocopherol REAL NOT NULL,
-            tocopherol_beta REAL NOT NULL,
-            tocopherol_delta REAL NOT NULL,
-            tocopherol_gamma REAL NOT NULL,
-            tryptophan REAL NOT NULL,
-            tyrosine REAL NOT NULL,
-            valine REAL NOT NULL,
-            vitamin_a_iu REAL NOT NULL,
-            vitamin_a_rae REAL NOT NULL,
-            vitamin_b12 REAL NOT NULL,
-            vitamin_b6 REAL NOT NULL,
-            vitamin_c_total_ascorbic_acid REAL NOT NULL,
-            vitamin_k_phylloquinone REAL NOT NULL,
-            dihydrophylloquinone REAL NOT NULL,
-            water REAL NOT NULL,
-            zinc_zn REAL NOT NULL,
-            tocotrienol_alpha REAL NOT NULL,
-            tocotrienol_beta REAL NOT NULL,
-            tocotrienol_gamma REAL NOT NULL,
-            tocotrienol_delta REAL NOT NULL
-        )",
-        [],
-    )?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS pantry (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            food_id INTEGER NOT NULL,
-            weight_grams REAL NOT NULL,
-            weight_grams_remaining REAL NOT NULL,
-            FOREIGN KEY (food_id) REFERENCES food_items (id)
-        )",
-        [],
-    )?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS recipes (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL UNIQUE
-        )",
-        [],
-    )?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS recipe_ingredients (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            recipe_id INTEGER NOT NULL,
-            food_id INTEGER NOT NULL,
-            weight_grams REAL NOT NULL,
-            FOREIGN KEY (recipe_id) REFERENCES recipes (id),
-            FOREIGN KEY (food_id) REFERENCES food_items (id),
-            UNIQUE (recipe_id, food_id)
-        )",
-        [],
-    )?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS entries (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            timestamp TEXT NOT NULL
-        )",
-        [],
-    )?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS entry_foods (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            entry_id INTEGER NOT NULL,
-            food_id INTEGER NOT NULL,
-            weight_grams REAL NOT NULL,
-            FOREIGN KEY (entry_id) REFERENCES entries (id),
-            FOREIGN KEY (food_id) REFERENCES food_items (id),
-            UNIQUE (entry_id, food_id)
-        )",
-        [],
-    )?;
+impl Database {
+    // Opens or creates the database file and brings its schema up to date.
+    // ":memory:" gives a throwaway database, which is handy for tests.
+    pub fn open(path: impl AsRef<Path>) -> Result<Database, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
 
-    let _ = conn.close();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
 
-    Ok(())
+        let mut conn = Connection::open(&path)?;
+        run_migrations(&mut conn)?;
+        sync_nutrient_dictionary(&mut conn)?;
+
+        Ok(Database { path, conn })
+    }
+}
+
+// Brings the nutrients table in line with the registry in nutrients.rs.
+// Placeholder rows for attr_ids added to the registry since are adopted
+// rather than duplicated, so their stored amounts stay attached.
+fn sync_nutrient_dictionary(conn: &mut Connection) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    for nutrient in NUTRIENTS {
+        let adopted = tx.execute(
+            "UPDATE nutrients SET key = ?1, name = ?2, unit = ?3 WHERE attr_id = ?4",
+            params![nutrient.key, nutrient.name, nutrient.unit.symbol(), nutrient.attr_id],
+        )?;
+
+        if adopted == 0 {
+            tx.execute(
+                "INSERT INTO nutrients (key, name, unit, attr_id) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (key) DO UPDATE SET name = excluded.name, unit = excluded.unit, attr_id = excluded.attr_id",
+                params![nutrient.key, nutrient.name, nutrient.unit.symbol(), nutrient.attr_id],
+            )?;
+        }
+    }
+
+    tx.commit()
 }
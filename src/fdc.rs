@@ -0,0 +1,333 @@
+// Reader for USDA FoodData Central bulk downloads
+// (https://fdc.nal.usda.gov/download-datasets): Foundation, SR Legacy,
+// Survey and Branded, as either the JSON file or the folder of CSVs.
+// FDC reports every amount per 100 g, and its nutrient numbers are the same
+// USDA numbers the registry is keyed on.
+
+use crate::data_entry::BASIS_GRAMS;
+use crate::import::{Batcher, ImportError};
+use crate::provider::{AmountBasis, ProviderFood};
+
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// Top-level keys of the JSON downloads.
+const JSON_LISTS: &[&str] = &["FoundationFoods", "SRLegacyFoods", "SurveyFoods", "BrandedFoods"];
+
+// Rows of food.csv worth importing; the rest are lab samples behind the
+// Foundation foods rather than foods themselves.
+const CSV_DATA_TYPES: &[&str] = &["foundation_food", "sr_legacy_food", "survey_fndds_food", "branded_food"];
+
+// Accepts the JSON file, the folder of CSVs, or any CSV inside that folder.
+pub fn read(path: &Path, batcher: &mut Batcher) -> Result<(), ImportError> {
+    let is_json = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+    if is_json {
+        read_json(path, batcher)
+    } else if path.is_dir() {
+        read_csv_dir(path, batcher)
+    } else {
+        match path.parent() {
+            Some(dir) => read_csv_dir(dir, batcher),
+            None => Err(ImportError::Format(format!("{} is not an FDC download", path.display()))),
+        }
+    }
+}
+
+// FDC writes numbers like "203" but some releases use "203.0".
+fn parse_nutrient_number(number: &str) -> Option<u32> {
+    number.trim().parse::<f32>().ok().map(|number| number as u32)
+}
+
+fn new_food(description: String) -> ProviderFood {
+    ProviderFood {
+        name: description.trim().to_string(),
+        serving_weight_grams: BASIS_GRAMS,
+        basis: AmountBasis::Per100Grams,
+        ..ProviderFood::default()
+    }
+}
+
+// Branded foods list a serving beside the per-100 g amounts. Servings given in
+// millilitres can't be turned into grams here, so only the label is kept.
+fn set_serving(food: &mut ProviderFood, size: Option<f32>, unit: Option<String>, household: Option<String>) {
+    let in_grams = unit.is_some_and(|unit| matches!(unit.trim().to_ascii_lowercase().as_str(), "g" | "grm"));
+
+    food.serving_grams = size.filter(|_| in_grams);
+    food.serving_label = household
+        .filter(|label| !label.trim().is_empty())
+        .or_else(|| food.serving_grams.map(|grams| format!("{} g", grams)));
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonFood {
+    description: String,
+    #[serde(default)]
+    brand_owner: Option<String>,
+    #[serde(default)]
+    brand_name: Option<String>,
+    #[serde(default)]
+    gtin_upc: Option<String>,
+    #[serde(default)]
+    serving_size: Option<f32>,
+    #[serde(default)]
+    serving_size_unit: Option<String>,
+    #[serde(default)]
+    household_serving_full_text: Option<String>,
+    #[serde(default)]
+    food_nutrients: Vec<JsonFoodNutrient>,
+}
+
+#[derive(Deserialize)]
+struct JsonFoodNutrient {
+    #[serde(default)]
+    nutrient: Option<JsonNutrient>,
+    #[serde(default)]
+    amount: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct JsonNutrient {
+    #[serde(default)]
+    number: Option<String>,
+}
+
+impl From<JsonFood> for ProviderFood {
+    fn from(json: JsonFood) -> ProviderFood {
+        let mut food = new_food(json.description);
+        food.brand = json.brand_name.or(json.brand_owner).filter(|brand| !brand.trim().is_empty());
+        food.barcode = json.gtin_upc.filter(|barcode| !barcode.trim().is_empty());
+        set_serving(&mut food, json.serving_size, json.serving_size_unit, json.household_serving_full_text);
+
+        for entry in json.food_nutrients {
+            let number = entry.nutrient.and_then(|nutrient| nutrient.number);
+            if let (Some(number), Some(amount)) = (number.as_deref().and_then(parse_nutrient_number), entry.amount) {
+                food.insert_usda(number, amount);
+            }
+        }
+
+        food
+    }
+}
+
+// The Branded download is several gigabytes, so foods are deserialized and
+// handed on one at a time instead of reading the whole document.
+fn read_json(path: &Path, batcher: &mut Batcher) -> Result<(), ImportError> {
+    let file = File::open(path).map_err(|err| ImportError::io(path, err))?;
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(file));
+
+    let mut cancelled = false;
+    let result = deserializer.deserialize_map(Document {
+        batcher,
+        cancelled: &mut cancelled,
+    });
+
+    match result {
+        _ if cancelled => Err(ImportError::Cancelled),
+        Ok(0) => Err(ImportError::Format(format!(
+            "{} has none of {}",
+            path.display(),
+            JSON_LISTS.join(", ")
+        ))),
+        Ok(_) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+struct Document<'a, 'b> {
+    batcher: &'a mut Batcher<'b>,
+    cancelled: &'a mut bool,
+}
+
+impl<'de> Visitor<'de> for Document<'_, '_> {
+    // How many food lists were found.
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an FDC JSON download")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<usize, A::Error> {
+        let mut lists = 0;
+
+        while let Some(key) = map.next_key::<String>()? {
+            if JSON_LISTS.contains(&key.as_str()) {
+                map.next_value_seed(FoodList {
+                    batcher: &mut *self.batcher,
+                    cancelled: &mut *self.cancelled,
+                })?;
+                lists += 1;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+
+        Ok(lists)
+    }
+}
+
+struct FoodList<'a, 'b> {
+    batcher: &'a mut Batcher<'b>,
+    cancelled: &'a mut bool,
+}
+
+impl<'de> DeserializeSeed<'de> for FoodList<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FoodList<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of foods")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(food) = seq.next_element::<JsonFood>()? {
+            if let Err(err) = self.batcher.push(food.into()) {
+                *self.cancelled = matches!(err, ImportError::Cancelled);
+                return Err(de::Error::custom(err));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct NutrientRow {
+    id: u32,
+    nutrient_nbr: String,
+}
+
+#[derive(Deserialize)]
+struct FoodRow {
+    fdc_id: u64,
+    data_type: String,
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct FoodNutrientRow {
+    fdc_id: u64,
+    nutrient_id: u32,
+    amount: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct BrandedRow {
+    fdc_id: u64,
+    #[serde(default)]
+    brand_owner: Option<String>,
+    #[serde(default)]
+    brand_name: Option<String>,
+    #[serde(default)]
+    gtin_upc: Option<String>,
+    #[serde(default)]
+    serving_size: Option<f32>,
+    #[serde(default)]
+    serving_size_unit: Option<String>,
+    #[serde(default)]
+    household_serving_fulltext: Option<String>,
+}
+
+fn csv_reader(dir: &Path, file: &str) -> Result<csv::Reader<File>, ImportError> {
+    let path = dir.join(file);
+    let file = File::open(&path).map_err(|err| ImportError::io(&path, err))?;
+    Ok(csv::Reader::from_reader(file))
+}
+
+// food_nutrient.csv holds one row per food and nutrient, so the foods are
+// assembled in memory before any are handed on.
+fn read_csv_dir(dir: &Path, batcher: &mut Batcher) -> Result<(), ImportError> {
+    let mut numbers: HashMap<u32, u32> = HashMap::new();
+    for row in csv_reader(dir, "nutrient.csv")?.deserialize() {
+        let row: NutrientRow = row?;
+        if let Some(number) = parse_nutrient_number(&row.nutrient_nbr) {
+            numbers.insert(row.id, number);
+        }
+    }
+
+    let mut foods: HashMap<u64, ProviderFood> = HashMap::new();
+    let mut order: Vec<u64> = Vec::new();
+    for row in csv_reader(dir, "food.csv")?.deserialize() {
+        let row: FoodRow = row?;
+        if CSV_DATA_TYPES.contains(&row.data_type.as_str()) {
+            order.push(row.fdc_id);
+            foods.insert(row.fdc_id, new_food(row.description));
+        }
+    }
+
+    // Only present in the Branded download.
+    if dir.join("branded_food.csv").exists() {
+        for row in csv_reader(dir, "branded_food.csv")?.deserialize() {
+            let row: BrandedRow = row?;
+            if let Some(food) = foods.get_mut(&row.fdc_id) {
+                food.brand = row.brand_name.or(row.brand_owner).filter(|brand| !brand.trim().is_empty());
+                food.barcode = row.gtin_upc.filter(|barcode| !barcode.trim().is_empty());
+                set_serving(food, row.serving_size, row.serving_size_unit, row.household_serving_fulltext);
+            }
+        }
+    }
+
+    for row in csv_reader(dir, "food_nutrient.csv")?.deserialize() {
+        let row: FoodNutrientRow = row?;
+        if let (Some(food), Some(&number), Some(amount)) = (foods.get_mut(&row.fdc_id), numbers.get(&row.nutrient_id), row.amount) {
+            food.insert_usda(number, amount);
+        }
+    }
+
+    for fdc_id in order {
+        if let Some(food) = foods.remove(&fdc_id) {
+            batcher.push(food)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::read_all;
+    use crate::nutrients::NutrientKind;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/fdc").join(name)
+    }
+
+    #[test]
+    fn reads_json_download() {
+        let foods = read_all(|batcher| read(&fixture("foundation.json"), batcher)).unwrap();
+
+        assert_eq!(foods.len(), 2);
+        assert_eq!(foods[0].name, "Hummus, commercial");
+        assert_eq!(foods[0].serving_weight_grams, 100.0);
+        assert_eq!(foods[0].nutrients.get(&NutrientKind::Protein), Some(&7.35));
+        assert_eq!(foods[1].brand.as_deref(), Some("Mahatma"));
+        assert_eq!(foods[1].barcode.as_deref(), Some("0041196010022"));
+        assert_eq!(foods[1].serving_grams, Some(45.0));
+    }
+
+    #[test]
+    fn reads_csv_download() {
+        let foods = read_all(|batcher| read(&fixture("csv/food.csv"), batcher)).unwrap();
+
+        // The sub-sample row in food.csv is skipped.
+        assert_eq!(foods.len(), 2);
+        assert_eq!(foods[0].name, "Hummus, commercial");
+        assert_eq!(foods[0].nutrients.get(&NutrientKind::Energy), Some(&229.0));
+        assert_eq!(foods[1].brand.as_deref(), Some("Mahatma"));
+        assert_eq!(foods[1].nutrients.get(&NutrientKind::Carbohydrate), Some(&80.0));
+    }
+}
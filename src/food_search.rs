@@ -0,0 +1,186 @@
+// Ranking for searches over stored foods. Every word of the query has to
+// match a word of the food's name, aliases or brand, either exactly, as its
+// start ("chick" for "chicken") or with a typo or two ("chiken"). SQLite
+// narrows the candidates with its FTS5 index first; both backends then rank
+// with `score` so they agree on the order.
+
+use crate::data_entry::Food;
+
+// Field weights: a match in the name counts for more than one in an alias,
+// which counts for more than one in the brand.
+const NAME_WEIGHT: f32 = 3.0;
+const ALIAS_WEIGHT: f32 = 2.0;
+const BRAND_WEIGHT: f32 = 1.0;
+
+// How well one query word matched, worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum WordMatch {
+    Typo,
+    Prefix,
+    Exact,
+}
+
+impl WordMatch {
+    fn points(self) -> f32 {
+        match self {
+            WordMatch::Typo => 1.0,
+            WordMatch::Prefix => 2.0,
+            WordMatch::Exact => 3.0,
+        }
+    }
+}
+
+// Lowercase words, split at anything that isn't a letter or digit, the way
+// FTS5's unicode61 tokenizer splits them.
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// Short words have too many neighbours to allow for typos at all.
+fn allowed_typos(word: &str) -> usize {
+    match word.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+fn word_match(query: &str, word: &str) -> Option<WordMatch> {
+    if query == word {
+        return Some(WordMatch::Exact);
+    }
+    if word.starts_with(query) {
+        return Some(WordMatch::Prefix);
+    }
+
+    // A typo in the whole word, or in what has been typed of it so far.
+    let allowed = allowed_typos(query);
+    let typed: String = word.chars().take(query.chars().count()).collect();
+    let close = |other: &str| edit_distance(query, other) <= allowed;
+    (allowed > 0 && (close(word) || close(&typed))).then_some(WordMatch::Typo)
+}
+
+// Whether `word` from the index could be what `query` meant, which is how
+// SQLite picks the index terms to look a query word up under.
+pub fn is_candidate(query: &str, word: &str) -> bool {
+    word_match(query, word).is_some()
+}
+
+fn best_match(query: &str, words: &[String]) -> Option<WordMatch> {
+    words.iter().filter_map(|word| word_match(query, word)).max()
+}
+
+// None unless every query word matches somewhere.
+pub fn score(food: &Food, query: &str) -> Option<f32> {
+    let query_words = words(query);
+    if query_words.is_empty() {
+        return None;
+    }
+
+    let name = words(&food.name);
+    let aliases: Vec<String> = food.aliases.iter().flat_map(|alias| words(alias)).collect();
+    let brand = food.brand.as_deref().map(words).unwrap_or_default();
+
+    let mut total = 0.0;
+    for query_word in &query_words {
+        let fields = [(&name, NAME_WEIGHT), (&aliases, ALIAS_WEIGHT), (&brand, BRAND_WEIGHT)];
+        let best = fields
+            .iter()
+            .filter_map(|(words, weight)| best_match(query_word, words).map(|found| found.points() * weight))
+            .reduce(f32::max)?;
+        total += best;
+    }
+
+    // Among equal matches, the food whose name starts with the query and
+    // then the one with fewer words is more likely the one meant.
+    if food.name.to_lowercase().starts_with(&query.trim().to_lowercase()) {
+        total += NAME_WEIGHT;
+    }
+    Some(total - name.len() as f32 * 0.01)
+}
+
+// The best `limit` of `foods` for `query`, best first. Ties keep the order
+// they came in.
+pub fn rank(foods: Vec<Food>, query: &str, limit: usize) -> Vec<Food> {
+    let mut scored: Vec<(f32, Food)> = foods
+        .into_iter()
+        .filter_map(|food| score(&food, query).map(|points| (points, food)))
+        .collect();
+    scored.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    scored.into_iter().take(limit).map(|(_, food)| food).collect()
+}
+
+// Damerau-Levenshtein distance with adjacent transpositions ("chciken").
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn food(name: &str, brand: Option<&str>, aliases: &[&str]) -> Food {
+        Food {
+            name: name.to_string(),
+            brand: brand.map(str::to_string),
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+            ..Food::default()
+        }
+    }
+
+    fn names(foods: &[Food]) -> Vec<&str> {
+        foods.iter().map(|food| food.name.as_str()).collect()
+    }
+
+    #[test]
+    fn typos_and_prefixes_match() {
+        assert_eq!(edit_distance("chciken", "chicken"), 1);
+        assert!(is_candidate("chiken", "chicken"));
+        assert!(is_candidate("chick", "chicken"));
+        assert!(is_candidate("chikc", "chicken"));
+        // Three letters is too short to guess at.
+        assert!(!is_candidate("egs", "eggs"));
+    }
+
+    #[test]
+    fn names_rank_above_aliases_and_brands() {
+        let foods = vec![
+            food("Oat milk", Some("Chicken Farms"), &[]),
+            food("Chicken breast, grilled", None, &[]),
+            food("Poultry", None, &["chicken"]),
+            food("Chicken", None, &[]),
+            food("Rice", None, &[]),
+        ];
+
+        assert_eq!(
+            names(&rank(foods.clone(), "chicken", 10)),
+            vec!["Chicken", "Chicken breast, grilled", "Poultry", "Oat milk"]
+        );
+        assert_eq!(names(&rank(foods.clone(), "grill chiken", 10)), vec!["Chicken breast, grilled"]);
+        assert_eq!(names(&rank(foods, "chicken", 1)), vec!["Chicken"]);
+    }
+}
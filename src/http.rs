@@ -0,0 +1,310 @@
+use crate::config::{self, Config};
+use crate::provider::QueryError;
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 15;
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+// Also the longest Retry-After we'll wait out; anything longer is reported.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+// One client for the whole app so connections are pooled. Timeouts are read
+// from the config file the first time it's needed.
+pub fn shared_client() -> Client {
+    CLIENT
+        .get_or_init(|| {
+            let config = Config::load();
+            let timeout = config.http_timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS);
+            let connect_timeout = config.http_connect_timeout_secs.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS);
+
+            Client::builder()
+                .timeout(Duration::from_secs(timeout))
+                .connect_timeout(Duration::from_secs(connect_timeout))
+                .build()
+                .unwrap_or_else(|_| Client::new())
+        })
+        .clone()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Config) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: config.http_max_retries.unwrap_or(DEFAULT_MAX_RETRIES),
+            base_backoff: BASE_BACKOFF,
+            max_backoff: MAX_BACKOFF,
+        }
+    }
+
+    // Doubles with every attempt, with up to half of it randomized so
+    // clients that failed together don't all retry together.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let full = self.base_backoff.saturating_mul(1 << attempt.min(16)).min(self.max_backoff);
+        full / 2 + full.mul_f64(fastrand::f64() / 2.0)
+    }
+}
+
+// Only failures a short wait can fix. Being offline isn't one of them; those
+// lookups are queued instead.
+fn worth_retrying(err: &QueryError) -> bool {
+    matches!(
+        err,
+        QueryError::Network(_) | QueryError::RateLimited { .. } | QueryError::Server { .. }
+    )
+}
+
+// Sends `request`, retrying transient failures with backoff and honouring
+// Retry-After. Every attempt that reaches the server counts against `quota`.
+pub async fn send_with_retry(
+    request: RequestBuilder,
+    policy: &RetryPolicy,
+    quota: &DailyQuota,
+) -> Result<Response, QueryError> {
+    let mut attempt = 0;
+
+    loop {
+        // Requests with streaming bodies can't be cloned, so they only get one try.
+        let this_try = match request.try_clone() {
+            Some(copy) => copy,
+            None => return send_counted(request, quota).await,
+        };
+
+        match send_counted(this_try, quota).await {
+            Err(err) if attempt < policy.max_retries && worth_retrying(&err) => {
+                let wait = match &err {
+                    QueryError::RateLimited { retry_after: Some(wait), .. } => *wait,
+                    _ => policy.backoff(attempt),
+                };
+                if wait > policy.max_backoff {
+                    return Err(err);
+                }
+
+                tokio::time::sleep(wait).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+// A request that never got a connection didn't reach the server, so it
+// gives its unit of quota back.
+async fn send_counted(request: RequestBuilder, quota: &DailyQuota) -> Result<Response, QueryError> {
+    quota.acquire()?;
+    let result = send_once(request).await;
+    if matches!(result, Err(QueryError::Offline(_))) {
+        quota.release();
+    }
+    result
+}
+
+async fn send_once(request: RequestBuilder) -> Result<Response, QueryError> {
+    match request.send().await {
+        Ok(response) => check_status(response).await,
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Failures are usually reported as `{"message": "..."}`.
+#[derive(Deserialize, Debug, Clone)]
+struct ErrorBody {
+    message: String,
+}
+
+async fn check_status(response: Response) -> Result<Response, QueryError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_retry_after(value, Utc::now()));
+    let message = error_message(response).await;
+
+    Err(QueryError::from_status(status, message, retry_after))
+}
+
+// Retry-After is either a number of seconds or an HTTP date, which is
+// turned into the wait from `now`. A date already past means no wait.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+// The server's explanation of a failed request, or the status text if the
+// body doesn't have one.
+async fn error_message(response: Response) -> String {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+
+    match serde_json::from_str::<ErrorBody>(&body) {
+        Ok(error) => error.message,
+        Err(_) if !body.trim().is_empty() => body.trim().to_string(),
+        Err(_) => status.canonical_reason().unwrap_or("Unknown error").to_string(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct QuotaUsage {
+    // UTC day the count applies to.
+    pub day: NaiveDate,
+    pub used: u32,
+    pub limit: u32,
+}
+
+// Client-side cap on requests per UTC day, saved to the data directory so it
+// carries across restarts.
+pub struct DailyQuota {
+    path: Option<PathBuf>,
+    usage: RefCell<QuotaUsage>,
+}
+
+impl DailyQuota {
+    pub fn load(name: &str, limit: u32) -> DailyQuota {
+        let path = config::data_dir().map(|dir| dir.join(format!("{}-quota.json", name)));
+        let today = Utc::now().date_naive();
+
+        let saved = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str::<QuotaUsage>(&text).ok())
+            .filter(|usage| usage.day == today);
+
+        DailyQuota {
+            path,
+            usage: RefCell::new(QuotaUsage {
+                day: today,
+                used: saved.map_or(0, |usage| usage.used),
+                limit,
+            }),
+        }
+    }
+
+    // A quota that isn't saved anywhere.
+    #[cfg(test)]
+    pub fn in_memory(limit: u32) -> DailyQuota {
+        DailyQuota {
+            path: None,
+            usage: RefCell::new(QuotaUsage {
+                day: Utc::now().date_naive(),
+                used: 0,
+                limit,
+            }),
+        }
+    }
+
+    pub fn usage(&self) -> QuotaUsage {
+        self.roll_over();
+        *self.usage.borrow()
+    }
+
+    // Counts one request, or refuses if today's allowance is used up.
+    pub fn acquire(&self) -> Result<(), QueryError> {
+        self.roll_over();
+        let mut usage = self.usage.borrow_mut();
+
+        if usage.used >= usage.limit {
+            let midnight = usage.day.checked_add_days(Days::new(1)).and_then(|day| day.and_hms_opt(0, 0, 0));
+            let retry_after = midnight
+                .and_then(|midnight| (midnight - Utc::now().naive_utc()).to_std().ok());
+
+            return Err(QueryError::RateLimited {
+                retry_after,
+                message: format!("all {} requests allowed today have been used", usage.limit),
+            });
+        }
+
+        usage.used += 1;
+        self.save(&usage);
+        Ok(())
+    }
+
+    // Hands back a request counted by `acquire` that was never sent.
+    pub fn release(&self) {
+        self.roll_over();
+        let mut usage = self.usage.borrow_mut();
+        usage.used = usage.used.saturating_sub(1);
+        self.save(&usage);
+    }
+
+    fn save(&self, usage: &QuotaUsage) {
+        if let Some(path) = &self.path {
+            // Losing the count only risks the server's own 429, so don't fail over it.
+            let _ = fs::create_dir_all(path.parent().unwrap_or(path))
+                .and_then(|_| fs::write(path, serde_json::to_string(usage).unwrap_or_default()));
+        }
+    }
+
+    fn roll_over(&self) {
+        let today = Utc::now().date_naive();
+        let mut usage = self.usage.borrow_mut();
+        if usage.day != today {
+            usage.day = today;
+            usage.used = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc2822("Wed, 01 May 2024 12:00:00 GMT").unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn retry_after_is_seconds_or_a_date() {
+        assert_eq!(parse_retry_after(" 120 ", now()), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 01 May 2024 12:01:30 GMT", now()),
+            Some(Duration::from_secs(90))
+        );
+        // A date that has already passed means trying again straight away.
+        assert_eq!(parse_retry_after("Wed, 01 May 2024 11:00:00 GMT", now()), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now()), None);
+        assert_eq!(parse_retry_after("-5", now()), None);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            base_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(4),
+        };
+
+        // Each wait is between half and all of the doubled base.
+        for (attempt, full) in [(0, 1), (1, 2), (2, 4), (5, 4)] {
+            let wait = policy.backoff(attempt);
+            let full = Duration::from_secs(full);
+            assert!(wait >= full / 2 && wait <= full, "attempt {}: {:?}", attempt, wait);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+// Shared plumbing for importing food datasets from disk. Readers push foods
+// into a Batcher; the batches are streamed back to the UI from a worker thread
+// and stored through the repository one transaction at a time.
+
+use crate::fdc;
+use crate::open_food_facts;
+use crate::provider::ProviderFood;
+
+use std::path::Path;
+use thiserror::Error;
+use tokio::sync::mpsc::{self, Receiver};
+
+const BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("Could not read {path}: {source}")]
+    Io { path: String, source: std::io::Error },
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid CSV: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("{0}")]
+    Format(String),
+    #[error("Import cancelled")]
+    Cancelled,
+}
+
+impl ImportError {
+    pub fn io(path: &Path, source: std::io::Error) -> ImportError {
+        ImportError::Io {
+            path: path.display().to_string(),
+            source,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dataset {
+    FoodDataCentral,
+    OpenFoodFacts,
+}
+
+impl Dataset {
+    pub fn all() -> &'static [Dataset] {
+        &[Dataset::FoodDataCentral, Dataset::OpenFoodFacts]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Dataset::FoodDataCentral => "USDA FoodData Central (JSON file or CSV folder)",
+            Dataset::OpenFoodFacts => "Open Food Facts (JSONL or CSV export, optionally .gz)",
+        }
+    }
+
+    pub fn read(self, path: &Path, batcher: &mut Batcher) -> Result<(), ImportError> {
+        match self {
+            Dataset::FoodDataCentral => fdc::read(path, batcher),
+            Dataset::OpenFoodFacts => open_food_facts::read(path, batcher),
+        }
+    }
+}
+
+pub type BatchSink<'a> = dyn FnMut(Vec<ProviderFood>) -> Result<(), ImportError> + 'a;
+
+// Collects foods and hands them to the sink BATCH_SIZE at a time.
+pub struct Batcher<'a> {
+    batch: Vec<ProviderFood>,
+    sink: &'a mut BatchSink<'a>,
+}
+
+impl<'a> Batcher<'a> {
+    pub fn new(sink: &'a mut BatchSink<'a>) -> Batcher<'a> {
+        Batcher {
+            batch: Vec::with_capacity(BATCH_SIZE),
+            sink,
+        }
+    }
+
+    pub fn push(&mut self, food: ProviderFood) -> Result<(), ImportError> {
+        self.batch.push(food);
+        if self.batch.len() >= BATCH_SIZE {
+            let full = std::mem::replace(&mut self.batch, Vec::with_capacity(BATCH_SIZE));
+            (self.sink)(full)?;
+        }
+        Ok(())
+    }
+
+    // Flushes whatever is left over.
+    pub fn finish(self) -> Result<(), ImportError> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        (self.sink)(self.batch)
+    }
+}
+
+pub type ImportMessage = Result<Vec<ProviderFood>, ImportError>;
+
+// Runs `read` on its own thread so a large dataset doesn't stall the window.
+// The receiver yields batches until the reader finishes or fails; dropping it
+// cancels the read.
+pub fn read_in_background<F>(read: F) -> Receiver<ImportMessage>
+where
+    F: FnOnce(&mut Batcher) -> Result<(), ImportError> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(4);
+
+    std::thread::spawn(move || {
+        let result = {
+            let mut send = |batch| tx.blocking_send(Ok(batch)).map_err(|_| ImportError::Cancelled);
+            let mut batcher = Batcher::new(&mut send);
+            read(&mut batcher).and_then(|()| batcher.finish())
+        };
+
+        if let Err(err) = result {
+            let _ = tx.blocking_send(Err(err));
+        }
+    });
+
+    rx
+}
+
+// Reads everything into memory, which is only sensible for small fixtures.
+#[cfg(test)]
+pub fn read_all(read: impl FnOnce(&mut Batcher) -> Result<(), ImportError>) -> Result<Vec<ProviderFood>, ImportError> {
+    let mut foods = Vec::new();
+    {
+        let mut collect = |batch: Vec<ProviderFood>| {
+            foods.extend(batch);
+            Ok(())
+        };
+        let mut batcher = Batcher::new(&mut collect);
+        read(&mut batcher)?;
+        batcher.finish()?;
+    }
+    Ok(foods)
+}
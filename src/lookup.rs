@@ -0,0 +1,318 @@
+use crate::data_entry::Food;
+use crate::provider::{normalize_barcode, NutritionProvider, ProviderFood, QueryError};
+use crate::repository::{Storage, StorageError};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use dioxus::prelude::*;
+use std::rc::Rc;
+use thiserror::Error;
+
+// How long a cached response is used before the provider is asked again.
+const CACHE_TTL_DAYS: i64 = 30;
+
+// Queued lookups are retried in the background after 1, 2, 4, ... minutes,
+// and left for the user to retry once this many attempts have failed.
+const RETRY_BASE_SECS: i64 = 60;
+const MAX_AUTOMATIC_ATTEMPTS: u32 = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedLookup {
+    pub foods: Vec<ProviderFood>,
+    pub fetched_at: NaiveDateTime,
+}
+
+impl CachedLookup {
+    pub fn is_fresh(&self, now: NaiveDateTime) -> bool {
+        now - self.fetched_at < Duration::days(CACHE_TTL_DAYS)
+    }
+}
+
+// A query that couldn't be answered when it was made, waiting in the queue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingLookup {
+    pub id: i64,
+    pub provider: String,
+    pub query: String,
+    pub queued_at: NaiveDateTime,
+    // When the placeholder entry was logged, if one was.
+    pub entry_timestamp: Option<NaiveDateTime>,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    // The provider answered but couldn't resolve the query, or it failed too
+    // often; only retried on request.
+    pub stalled: bool,
+}
+
+impl PendingLookup {
+    // Whether the background retry should try it again yet. The wait doubles
+    // with every failed attempt, counted from when it was queued so it holds
+    // across restarts.
+    pub fn is_due(&self, now: NaiveDateTime) -> bool {
+        let waits = (1i64 << self.attempts.min(MAX_AUTOMATIC_ATTEMPTS)) - 1;
+        now - self.queued_at >= Duration::seconds(RETRY_BASE_SECS * waits)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ResolveError {
+    #[error(transparent)]
+    Query(#[from] QueryError),
+    #[error("The foods were found but couldn't be saved: {0}")]
+    Storage(#[from] StorageError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LookupSource {
+    Provider,
+    Cache,
+    // Past its TTL, but the provider couldn't be reached to replace it.
+    StaleCache,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lookup {
+    pub foods: Vec<ProviderFood>,
+    pub source: LookupSource,
+    pub fetched_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BarcodeMatch {
+    // Already in the database, so there is nothing to add.
+    Stored(Food),
+    Provider(ProviderFood),
+}
+
+// "1 Cup  Rice " and "1 cup rice" should share a cache entry.
+pub fn normalize_query(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// Parses `query` with `provider`, answering from the cache when it has a
+// fresh response. `force_refresh` skips the cache unless the provider can't
+// be reached, in which case any cached response beats an error.
+pub async fn parse_foods(
+    mut store: Signal<Box<dyn Storage>>,
+    provider: Rc<dyn NutritionProvider>,
+    query: &str,
+    force_refresh: bool,
+) -> Result<Lookup, QueryError> {
+    let key = normalize_query(query);
+    let now = Utc::now().naive_utc();
+
+    // A cache that can't be read is treated as empty rather than failing the lookup.
+    let cached = store.read().cached_lookup(provider.name(), &key).ok().flatten();
+
+    if let Some(cached) = &cached {
+        if !force_refresh && cached.is_fresh(now) {
+            return Ok(Lookup {
+                foods: cached.foods.clone(),
+                source: LookupSource::Cache,
+                fetched_at: cached.fetched_at,
+            });
+        }
+    }
+
+    match provider.parse(query).await {
+        Ok(foods) => {
+            // Likewise, failing to cache shouldn't throw away a good response.
+            let _ = store.write().cache_lookup(provider.name(), &key, &foods, now);
+            Ok(Lookup {
+                foods,
+                source: LookupSource::Provider,
+                fetched_at: now,
+            })
+        }
+        Err(err) if err.is_retryable() => match cached {
+            Some(cached) => Ok(Lookup {
+                source: if cached.is_fresh(now) { LookupSource::Cache } else { LookupSource::StaleCache },
+                foods: cached.foods,
+                fetched_at: cached.fetched_at,
+            }),
+            None => Err(err),
+        },
+        Err(err) => Err(err),
+    }
+}
+
+// Resolves a typed or scanned UPC/EAN, preferring a food already stored
+// under it over asking the provider.
+pub async fn lookup_barcode(
+    store: Signal<Box<dyn Storage>>,
+    provider: Rc<dyn NutritionProvider>,
+    code: &str,
+) -> Result<BarcodeMatch, QueryError> {
+    let barcode = match normalize_barcode(code) {
+        Some(barcode) => barcode,
+        None => {
+            return Err(QueryError::BadRequest(format!(
+                "\"{}\" is not a UPC or EAN barcode",
+                code.trim()
+            )))
+        }
+    };
+
+    if let Ok(Some(food)) = store.read().food_by_barcode(&barcode) {
+        return Ok(BarcodeMatch::Stored(food));
+    }
+
+    provider.lookup_barcode(&barcode).await.map(BarcodeMatch::Provider)
+}
+
+// Tries one queued lookup, back-filling its placeholder entry on success.
+// Failures are recorded on the lookup so they show next to it.
+pub async fn resolve_pending(
+    mut store: Signal<Box<dyn Storage>>,
+    provider: Rc<dyn NutritionProvider>,
+    lookup: &PendingLookup,
+) -> Result<(), ResolveError> {
+    let result = match parse_foods(store, provider, &lookup.query, false).await {
+        Ok(found) => store.write().resolve_lookup(lookup.id, &found.foods).map_err(ResolveError::from),
+        Err(err) => Err(err.into()),
+    };
+
+    if let Err(err) = &result {
+        let stalled = match err {
+            ResolveError::Query(err) => !err.is_retryable() || lookup.attempts + 1 >= MAX_AUTOMATIC_ATTEMPTS,
+            // Trying again would most likely fail the same way.
+            ResolveError::Storage(_) => true,
+        };
+        let _ = store.write().record_lookup_failure(lookup.id, &err.to_string(), stalled);
+    }
+    result
+}
+
+// Works through the lookups for `provider` that are due a retry, stopping at
+// the first failure that suggests it still can't be reached. Returns how many
+// lookups resolved.
+pub async fn resolve_all_pending(store: Signal<Box<dyn Storage>>, provider: Rc<dyn NutritionProvider>) -> usize {
+    let pending = store.read().pending_lookups().unwrap_or_default();
+    let now = Utc::now().naive_utc();
+    let mut resolved = 0;
+
+    for lookup in pending
+        .iter()
+        .filter(|lookup| lookup.provider == provider.name() && !lookup.stalled && lookup.is_due(now))
+    {
+        match resolve_pending(store, provider.clone(), lookup).await {
+            Ok(()) => resolved += 1,
+            Err(ResolveError::Query(err)) if err.is_retryable() => break,
+            Err(_) => {}
+        }
+    }
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credentials::Credentials;
+    use crate::http::DailyQuota;
+    use crate::nutrition_api::Nutritionix;
+    use crate::repository::InMemoryRepository;
+    use crate::stub_server::{StubServer, APP_ID, APP_KEY};
+
+    const QUERY: &str = "1 cup rice and 2 eggs";
+
+    fn nutritionix(base_url: &str) -> Rc<dyn NutritionProvider> {
+        let credentials = Credentials {
+            app_id: APP_ID.to_string(),
+            app_key: APP_KEY.to_string(),
+        };
+        Rc::new(
+            Nutritionix::new(Some(credentials))
+                .with_base_url(base_url)
+                .with_quota(DailyQuota::in_memory(100)),
+        )
+    }
+
+    // A store holding one cached response for QUERY, fetched `age` ago.
+    // Signals live in a component scope, so the store is made in the root
+    // scope of an otherwise empty dom, which has to outlive it.
+    fn store_with_cached(provider: &dyn NutritionProvider, age: Duration) -> (VirtualDom, Signal<Box<dyn Storage>>) {
+        let mut repository: Box<dyn Storage> = Box::new(InMemoryRepository::new());
+        let cached = vec![ProviderFood {
+            name: "cached rice".to_string(),
+            ..ProviderFood::default()
+        }];
+        let fetched_at = Utc::now().naive_utc() - age;
+        repository.cache_lookup(provider.name(), &normalize_query(QUERY), &cached, fetched_at).unwrap();
+
+        let mut dom = VirtualDom::new(|| None);
+        dom.rebuild_in_place();
+        let store = dom.in_runtime(|| ScopeId::ROOT.in_runtime(|| Signal::new(repository)));
+        (dom, store)
+    }
+
+    #[tokio::test]
+    async fn fresh_cache_answers_without_the_provider() {
+        let server = StubServer::nutritionix();
+        let provider = nutritionix(&server.url());
+        let (_dom, store) = store_with_cached(provider.as_ref(), Duration::days(1));
+
+        let lookup = parse_foods(store, provider.clone(), "1 Cup  rice and 2 eggs ", false).await.unwrap();
+        assert_eq!(lookup.source, LookupSource::Cache);
+        assert_eq!(lookup.foods[0].name, "cached rice");
+        assert!(server.requests().is_empty());
+
+        // Unless a refresh is asked for.
+        let lookup = parse_foods(store, provider, QUERY, true).await.unwrap();
+        assert_eq!(lookup.source, LookupSource::Provider);
+        assert_eq!(lookup.foods[0].name, "rice");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn expired_cache_is_fetched_again() {
+        let server = StubServer::nutritionix();
+        let provider = nutritionix(&server.url());
+        let (_dom, store) = store_with_cached(provider.as_ref(), Duration::days(CACHE_TTL_DAYS + 1));
+
+        let lookup = parse_foods(store, provider.clone(), QUERY, false).await.unwrap();
+        assert_eq!(lookup.source, LookupSource::Provider);
+        assert_eq!(server.requests().len(), 1);
+
+        // The new response replaces the expired one.
+        let cached = store.read().cached_lookup(provider.name(), &normalize_query(QUERY)).unwrap().unwrap();
+        assert!(cached.is_fresh(Utc::now().naive_utc()));
+        assert_eq!(cached.foods[0].name, "rice");
+    }
+
+    #[tokio::test]
+    async fn expired_cache_still_answers_offline() {
+        // Bind and drop a listener to get a port nothing is listening on.
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let provider = nutritionix(&format!("http://127.0.0.1:{}", port));
+        let (_dom, store) = store_with_cached(provider.as_ref(), Duration::days(CACHE_TTL_DAYS + 1));
+
+        let lookup = parse_foods(store, provider, QUERY, true).await.unwrap();
+        assert_eq!(lookup.source, LookupSource::StaleCache);
+        assert_eq!(lookup.foods[0].name, "cached rice");
+    }
+
+    #[test]
+    fn retries_back_off() {
+        let queued_at = NaiveDateTime::parse_from_str("2024-05-01 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let lookup = |attempts| PendingLookup {
+            id: 1,
+            provider: "Nutritionix".to_string(),
+            query: "2 eggs".to_string(),
+            queued_at,
+            entry_timestamp: None,
+            attempts,
+            last_error: None,
+            stalled: false,
+        };
+        let after = |minutes| queued_at + Duration::minutes(minutes);
+
+        assert!(lookup(0).is_due(queued_at));
+        assert!(!lookup(1).is_due(after(0)));
+        assert!(lookup(1).is_due(after(1)));
+        // 1 + 2 minutes of waiting after two failures, then 1 + 2 + 4.
+        assert!(!lookup(2).is_due(after(2)));
+        assert!(lookup(2).is_due(after(3)));
+        assert!(!lookup(3).is_due(after(6)));
+        assert!(lookup(3).is_due(after(7)));
+    }
+}
@@ -2,21 +2,107 @@ use dioxus::prelude::*;
 
 mod nutrition_api;
 mod data_entry;
+mod migrations;
+mod nutrients;
+mod provider;
+mod credentials;
+mod config;
+mod repository;
+mod lookup;
+mod http;
+mod import;
+mod fdc;
+mod open_food_facts;
+mod barcode;
+mod nutrition_label;
+mod measures;
+mod food_search;
+#[cfg(test)]
+mod stub_server;
 
-use crate::nutrition_api::{ApiFood, Nutrient, query_nutritionx, get_nutrient_name_map};
-use crate::data_entry::{Food, create_database, add_food_items, get_foods};
+use crate::nutrition_api::{Nutrient, Nutritionix};
+use crate::data_entry::{Food, PantryItem, BASIS_GRAMS};
+use crate::provider::{NutritionProvider, ProviderFood, QueryError, SearchHit};
+use crate::data_entry::NutrientTotal;
+use crate::nutrients::{NutrientCategory, NutrientKind};
+use crate::config::{Config, in_memory_requested, resolve_db_path};
+use crate::repository::{
+    StorageError, AddOutcome, ConflictStrategy, FoodReferences, ImportReport, InMemoryRepository, ReferenceHandling, SqliteRepository,
+    Storage,
+};
+use crate::credentials::{CredentialStore, Credentials};
+use crate::import::{read_in_background, Dataset};
+use crate::lookup::{
+    lookup_barcode, normalize_query, parse_foods, resolve_all_pending, resolve_pending, BarcodeMatch, LookupSource, PendingLookup,
+};
+use crate::barcode::decode_file_in_background;
+use crate::nutrition_label::{LabelBasis, NutritionLabel, LABEL_NUTRIENTS};
+use crate::measures::{to_grams, Measure};
+
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
 
 fn main() {
-    create_database();
+    // Open once up front so a bad path or a too-new schema fails loudly
+    // instead of inside the window.
+    let db_path = resolve_db_path();
+    if !in_memory_requested() {
+        if let Err(err) = SqliteRepository::open(&db_path) {
+            eprintln!("Could not open {}: {}", db_path.display(), err);
+            std::process::exit(1);
+        }
+    }
+
+    launch(App);
+}
+
+#[component]
+fn App() -> Element {
+    use_context_provider(|| {
+        let repository: Box<dyn Storage> = if in_memory_requested() {
+            Box::new(InMemoryRepository::new())
+        } else {
+            Box::new(SqliteRepository::open(resolve_db_path()).expect("database was opened at startup"))
+        };
+        Signal::new(repository)
+    });
+    let store = use_context::<Store>();
+    use_context_provider(|| Signal::new(Rc::new(Nutritionix::from_settings()) as Rc<dyn NutritionProvider>));
+    let provider = use_context::<Provider>();
 
-    launch(|| {
-        rsx! {
-            style { {include_str!("../assets/style.css")} }
-            Router::<Route> {}
+    // Back-fills queued lookups whenever the provider can be reached again,
+    // trying each one less often the more it has failed.
+    use_future(move || async move {
+        loop {
+            let current = provider.read().clone();
+            resolve_all_pending(store, current).await;
+            tokio::time::sleep(PENDING_RETRY_INTERVAL).await;
         }
     });
+
+    rsx! {
+        style { {include_str!("../assets/style.css")} }
+        Router::<Route> {}
+    }
 }
 
+// The storage backend every component reads from, swapped out in Settings.
+type Store = Signal<Box<dyn Storage>>;
+
+// Where food lookups go; rebuilt when credentials change in Settings.
+type Provider = Signal<Rc<dyn NutritionProvider>>;
+
+const PENDING_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+// Typeahead waits this long after the last keystroke before searching, and
+// ignores anything shorter than MIN_SEARCH_CHARS, so requests aren't spent on
+// every letter.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(350);
+const MIN_SEARCH_CHARS: usize = 3;
+
 // Turn off rustfmt since we're doing layouts and routes in the same enum
 #[derive(Routable, Clone, Debug, PartialEq)]
 #[rustfmt::skip]
@@ -26,6 +112,9 @@ enum Route {
         #[route("/")]
         Home {},
 
+        #[route("/settings")]
+        Settings {},
+
         #[nest("/nutrition")]
         #[layout(NutritionNavBar)]
             // At "/blog", we want to show a list of blog posts
@@ -38,6 +127,9 @@ enum Route {
             #[route("/addfoods")]
             AddFoods {},
 
+            #[route("/customfood")]
+            CustomFood {},
+
             #[route("/addpantryitem")]
             AddPantryItem {},
 
@@ -47,6 +139,9 @@ enum Route {
             #[route("/foods")]
             Foods {},
 
+            #[route("/foods/:name")]
+            FoodDetail { name: String },
+
             
 
             // At "/blog/:name", we want to show a specific blog post, using the name slug
@@ -86,6 +181,7 @@ fn HomeNavBar() -> Element {
                 li { Link { to: Route::Home {}, "Home" } }
                 li { Link { to: Route::Nutrition {}, "Nutrition" } }
                 li { Link { to: Route::Excersize {}, "Excersize" } }
+                li { Link { to: Route::Settings {}, "Settings" } }
             }
         }
         Outlet::<Route> {}
@@ -99,7 +195,8 @@ fn NutritionNavBar() -> Element {
             ul {
                 li { Link { to: Route::Entry{}, "Entry" } }
                 li { Link { to: Route::AddFoods{}, "AddFoods" } }
-                li { Link { to: Route::AddPantryItem {}, "AddPantryItem" } }
+                li { Link { to: Route::CustomFood{}, "CustomFood" } }
+                li { Link { to: Route::AddPantryItem {}, "Pantry" } }
                 li { Link { to: Route::AddRecipe {}, "Recipes" } }
                 li { Link { to: Route::Foods{}, "Foods" } }
                 
@@ -132,6 +229,346 @@ fn Home() -> Element {
     }
 }
 
+#[component]
+fn Settings() -> Element {
+    let mut store = use_context::<Store>();
+    let mut config = use_signal(Config::load);
+    let mut path_text = use_signal(|| String::new());
+    let mut error = use_signal(|| None::<String>);
+
+    let mut open_database = move |path: PathBuf| {
+        match SqliteRepository::open(&path) {
+            Ok(opened) => {
+                store.set(Box::new(opened));
+                config.write().remember_database(&path);
+                error.set(config.read().save().err().map(|err| err.to_string()));
+            }
+            Err(err) => error.set(Some(format!("Could not open {}: {}", path.display(), err))),
+        }
+    };
+
+    let current_path = store.read().location();
+    let config_lock = config.read();
+
+    rsx! {
+        h2 { "Database" }
+        p { "Current file: {current_path}" }
+
+        input {
+            r#type: "text",
+            placeholder: "Path to a .db file",
+            value: "{path_text}",
+            oninput: move |event| path_text.set(event.value()),
+        }
+        button {
+            onclick: move |_| {
+                let path = path_text().trim().to_string();
+                if !path.is_empty() {
+                    open_database(PathBuf::from(path));
+                    path_text.set(String::new());
+                }
+            },
+            "Open or create"
+        }
+
+        if let Some(err) = error() {
+            p { class: "error", "{err}" }
+        }
+
+        if !config_lock.recent_databases.is_empty() {
+            h3 { "Recent files" }
+            ul {
+                for path in config_lock.recent_databases.iter().cloned() {
+                    li {
+                        button {
+                            onclick: move |_| open_database(path.clone()),
+                            "{path.display()}"
+                        }
+                    }
+                }
+            }
+        }
+
+        CredentialSettings {}
+        ImportFoods {}
+    }
+}
+
+#[component]
+fn ImportFoods() -> Element {
+    let mut store = use_context::<Store>();
+    let mut dataset = use_signal(|| Dataset::FoodDataCentral);
+    let mut path_text = use_signal(|| String::new());
+    let on_conflict = use_signal(ConflictStrategy::default);
+    let mut report = use_signal(|| None::<ImportReport>);
+    let mut running = use_signal(|| false);
+    let mut error = use_signal(|| None::<String>);
+
+    let mut start_import = move |path: PathBuf| {
+        let chosen = dataset();
+        let on_conflict = on_conflict();
+        running.set(true);
+        report.set(Some(ImportReport::default()));
+        error.set(None);
+
+        let mut batches = read_in_background(move |batcher| chosen.read(&path, batcher));
+        spawn(async move {
+            while let Some(message) = batches.recv().await {
+                let added = message
+                    .map_err(|err| err.to_string())
+                    .and_then(|batch| store.write().import_foods(batch, on_conflict).map_err(|err| err.to_string()));
+
+                match added {
+                    Ok(added) => {
+                        if let Some(report) = report.write().as_mut() {
+                            *report += added;
+                        }
+                    }
+                    // Dropping `batches` on the way out stops the reader.
+                    Err(err) => {
+                        error.set(Some(format!("Import stopped: {}", err)));
+                        break;
+                    }
+                }
+            }
+            running.set(false);
+        });
+    };
+
+    rsx! {
+        h2 { "Import foods" }
+        p { "Load a dataset downloaded to this computer. Each batch of foods is stored in full or not at all." }
+
+        select {
+            onchange: move |event| {
+                if let Some(chosen) = Dataset::all().iter().find(|chosen| chosen.label() == event.value()) {
+                    dataset.set(*chosen);
+                }
+            },
+            for choice in Dataset::all() {
+                option {
+                    value: "{choice.label()}",
+                    selected: *choice == dataset(),
+                    "{choice.label()}"
+                }
+            }
+        }
+        input {
+            r#type: "text",
+            placeholder: "Path to the downloaded file",
+            value: "{path_text}",
+            oninput: move |event| path_text.set(event.value()),
+        }
+        button {
+            disabled: running(),
+            onclick: move |_| {
+                let path = path_text.read().trim().to_string();
+                if !path.is_empty() {
+                    start_import(PathBuf::from(path));
+                }
+            },
+            "Import"
+        }
+        ConflictChoice { choice: on_conflict }
+
+        if let Some(report) = report() {
+            p {
+                if running() { "Importing… " }
+                {describe_import(&report)}
+            }
+        }
+        if let Some(error) = error() {
+            p { class: "error", "{error}" }
+        }
+    }
+}
+
+fn describe_import(report: &ImportReport) -> String {
+    let mut parts = vec![format!("{} foods added", report.imported)];
+    if report.renamed > 0 {
+        parts.push(format!("{} added under a numbered name", report.renamed));
+    }
+    if report.overwritten > 0 {
+        parts.push(format!("{} overwritten", report.overwritten));
+    }
+    if report.merged > 0 {
+        parts.push(format!("{} merged", report.merged));
+    }
+    if report.skipped > 0 {
+        parts.push(format!("{} skipped because the name is already stored", report.skipped));
+    }
+    format!("{}.", parts.join(", "))
+}
+
+// Picks how foods whose name is already stored are handled.
+#[component]
+fn ConflictChoice(choice: Signal<ConflictStrategy>) -> Element {
+    rsx! {
+        label {
+            "If a food with the same name is already stored: "
+            select {
+                onchange: move |event| {
+                    if let Some(chosen) = ConflictStrategy::all().iter().find(|chosen| chosen.label() == event.value()) {
+                        choice.set(*chosen);
+                    }
+                },
+                for strategy in ConflictStrategy::all() {
+                    option {
+                        value: "{strategy.label()}",
+                        selected: *strategy == choice(),
+                        "{strategy.label()}"
+                    }
+                }
+            }
+        }
+    }
+}
+
+// How many matches FoodPicker lists at once.
+const PICKER_RESULTS: usize = 8;
+
+// Finds a stored food as its name, an alias or its brand is typed, and sets
+// `selected` to the name of the one picked. Typing again clears it.
+#[component]
+fn FoodPicker(selected: Signal<String>, exclude: Option<String>) -> Element {
+    let store = use_context::<Store>();
+    let mut query = use_signal(|| selected.read().clone());
+
+    let matches: Vec<Food> = if query.read().trim().is_empty() || query() == selected() {
+        Vec::new()
+    } else {
+        store
+            .read()
+            .search_foods(&query(), PICKER_RESULTS)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|food| Some(&food.name) != exclude.as_ref())
+            .collect()
+    };
+
+    let mut pick = move |name: String| {
+        query.set(name.clone());
+        selected.set(name);
+    };
+
+    rsx! {
+        div { class: "food-picker",
+            input {
+                r#type: "search",
+                placeholder: "Find a food…",
+                value: "{query}",
+                oninput: move |event| {
+                    query.set(event.value());
+                    selected.set(String::new());
+                },
+            }
+            if !matches.is_empty() {
+                ul { class: "search-hits",
+                    for food in matches {
+                        li { key: "{food.name}",
+                            button {
+                                onclick: {
+                                    let name = food.name.clone();
+                                    move |_| pick(name.clone())
+                                },
+                                "{food.name}"
+                                if let Some(brand) = &food.brand {
+                                    span { class: "barcode", " {brand}" }
+                                }
+                            }
+                        }
+                    }
+                }
+            } else if !query.read().trim().is_empty() && selected.read().is_empty() {
+                p { class: "hint", "No stored food matches \"{query}\"." }
+            }
+        }
+    }
+}
+
+#[component]
+fn CredentialSettings() -> Element {
+    let mut provider = use_context::<Provider>();
+    let mut resolved = use_signal(Credentials::resolve);
+    let mut app_id = use_signal(|| String::new());
+    let mut app_key = use_signal(|| String::new());
+    let mut message = use_signal(|| None::<String>);
+
+    let mut refresh = move || {
+        resolved.set(Credentials::resolve());
+        provider.set(Rc::new(Nutritionix::from_settings()));
+    };
+
+    let status = match resolved() {
+        Some((_, source)) => format!("Using credentials from the {}.", source.describe()),
+        None => "No credentials configured; Nutritionix lookups will fail until they are set.".to_string(),
+    };
+    let quota = describe_quota(provider.read().as_ref());
+
+    rsx! {
+        h2 { "Nutritionix credentials" }
+        p { "{status}" }
+        if let Some(quota) = quota {
+            p { class: "quota", "{quota}" }
+        }
+        p { "NUTRITIONIX_APP_ID and NUTRITIONIX_APP_KEY, or the config file, take precedence over what is saved here." }
+
+        input {
+            r#type: "text",
+            placeholder: "App ID",
+            value: "{app_id}",
+            oninput: move |event| app_id.set(event.value()),
+        }
+        input {
+            r#type: "password",
+            placeholder: "App key",
+            value: "{app_key}",
+            oninput: move |event| app_key.set(event.value()),
+        }
+        button {
+            onclick: move |_| {
+                let credentials = Credentials {
+                    app_id: app_id().trim().to_string(),
+                    app_key: app_key().trim().to_string(),
+                };
+                if credentials.app_id.is_empty() || credentials.app_key.is_empty() {
+                    message.set(Some("Enter both the app ID and the app key.".to_string()));
+                    return;
+                }
+
+                let saved = CredentialStore::default_location()
+                    .ok_or_else(|| "No data directory on this platform".into())
+                    .and_then(|store| store.save(&credentials));
+                match saved {
+                    Ok(()) => {
+                        app_id.set(String::new());
+                        app_key.set(String::new());
+                        message.set(Some("Saved.".to_string()));
+                    }
+                    Err(err) => message.set(Some(format!("Could not save credentials: {}", err))),
+                }
+                refresh();
+            },
+            "Save"
+        }
+        button {
+            onclick: move |_| {
+                if let Some(store) = CredentialStore::default_location() {
+                    if let Err(err) = store.clear() {
+                        message.set(Some(format!("Could not clear credentials: {}", err)));
+                    }
+                }
+                refresh();
+            },
+            "Clear saved"
+        }
+
+        if let Some(message) = message() {
+            p { "{message}" }
+        }
+    }
+}
+
 #[component]
 fn Nutrition() -> Element {
     rsx! {
@@ -140,67 +577,889 @@ fn Nutrition() -> Element {
 
 #[component]
 fn Entry() -> Element {
+    let mut store = use_context::<Store>();
+    let mut totals = use_signal(|| BTreeMap::new());
+    let food = use_signal(|| String::new());
+    let mut amount = use_signal(|| String::new());
+    let mut message = use_signal(|| None::<String>);
+    let mut load_error = use_signal(|| None::<String>);
+
+    use_effect(move || {
+        match store.read().daily_totals(Utc::now().date_naive()) {
+            Ok(today) => {
+                totals.set(today);
+                load_error.set(None);
+            }
+            Err(err) => load_error.set(Some(err.to_string())),
+        }
+    });
+
+    // What the amount comes to, as it's typed.
+    let chosen = store.read().food_named(&food()).ok().flatten();
+    let preview = chosen
+        .filter(|_| !amount.read().trim().is_empty())
+        .map(|chosen| to_grams(&chosen, &amount()));
+
+    let log = move |_| {
+        let portion = (food(), amount());
+        let result = store.write().log_entry(Utc::now().naive_utc(), &[portion]);
+        match result {
+            Ok(()) => {
+                message.set(Some(format!("Logged {} of {}.", amount(), food())));
+                amount.set(String::new());
+            }
+            Err(err) => message.set(Some(err.to_string())),
+        }
+    };
+
+    let grouped = totals_by_category(&totals.read());
+
     rsx! {
-        h2 { "Foods" }
+        h2 { "Log food" }
+        FoodPicker { selected: food }
+        input {
+            r#type: "text",
+            placeholder: "e.g. 2 slices, 1/2 cup or 150 g",
+            value: "{amount}",
+            oninput: move |event| amount.set(event.value()),
+        }
+        button {
+            disabled: !matches!(preview, Some(Ok(_))),
+            onclick: log,
+            "Log"
+        }
+        match &preview {
+            Some(Ok(grams)) => rsx! { span { class: "hint", " {grams:.0} g" } },
+            Some(Err(err)) => rsx! { span { class: "hint", " {err}" } },
+            None => rsx! {},
+        }
+        if let Some(message) = message() {
+            p { "{message}" }
+        }
+
+        h2 { "Today" }
+        if let Some(err) = load_error() {
+            p { class: "error", "{err}" }
+        }
+        for (category, totals) in grouped {
+            h3 { "{category.label()}" }
+            ul {
+                for (kind, total) in totals {
+                    li { {describe_total(kind, &total)} }
+                }
+            }
+        }
+    }
+}
+
+fn describe_total(kind: NutrientKind, total: &NutrientTotal) -> String {
+    if total.unknown_count == total.food_count {
+        format!("{}: not reported", kind)
+    } else if total.is_complete() {
+        format!("{}: {:.2} {}", kind, total.amount, kind.unit())
+    } else {
+        format!(
+            "{}: {:.2} {} ({} of {} foods not reported)",
+            kind, total.amount, kind.unit(), total.unknown_count, total.food_count
+        )
+    }
+}
+
+// Groups totals under their category so the page reads macros first.
+fn totals_by_category(
+    totals: &BTreeMap<NutrientKind, NutrientTotal>,
+) -> BTreeMap<NutrientCategory, Vec<(NutrientKind, NutrientTotal)>> {
+    let mut grouped: BTreeMap<NutrientCategory, Vec<(NutrientKind, NutrientTotal)>> = BTreeMap::new();
+
+    for (kind, total) in totals {
+        grouped.entry(kind.category()).or_default().push((*kind, *total));
     }
+
+    grouped
+}
+
+// How AddFoods finds foods: a free-text description, a search to pick from,
+// or a package barcode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AddMode {
+    Describe,
+    Search,
+    Barcode,
 }
 
 #[component]
 fn AddFoods() -> Element {
+    let mut store = use_context::<Store>();
+    let provider = use_context::<Provider>();
+    let mut mode = use_signal(|| AddMode::Describe);
     let mut text = use_signal(|| String::new());
+    let mut image_path = use_signal(|| String::new());
+    let mut stored = use_signal(|| None::<Food>);
+    let mut hits = use_signal(Vec::<SearchHit>::new);
+    let mut searched = use_signal(HashMap::<String, Vec<SearchHit>>::new);
+    let mut search_generation = use_signal(|| 0u32);
+    let mut picked = use_signal(|| None::<String>);
 
     let mut foods = use_signal(|| Vec::new());
-    let foods_lock = foods.read();
 
-    let nutrient_name_map = get_nutrient_name_map();
+    let mut error = use_signal(|| None::<QueryError>);
+    let mut last_query = use_signal(|| String::new());
+    let mut source = use_signal(|| None::<(LookupSource, String)>);
+    let mut queued = use_signal(|| None::<(i64, String)>);
+    let mut logged = use_signal(|| false);
+    let mut error_note = use_signal(|| None::<String>);
+
+    let mut run_query = move |query_input: String, force_refresh: bool| {
+        let provider = provider.read().clone();
+        last_query.set(query_input.clone());
+        error.set(None);
+        source.set(None);
+        queued.set(None);
+        logged.set(false);
+        stored.set(None);
+
+        spawn(async move {
+            match parse_foods(store, provider.clone(), &query_input, force_refresh).await {
+                Ok(lookup) => {
+                    let fetched_at = lookup.fetched_at.format("%Y-%m-%d %H:%M UTC").to_string();
+                    source.set(Some((lookup.source, fetched_at)));
+                    foods.set(review_rows(lookup.foods));
+                }
+                // Keep what was typed so it can be looked up once the provider is back.
+                Err(err) if err.is_retryable() => {
+                    let queued_at = Utc::now().naive_utc();
+                    if let Ok(id) = store.write().queue_lookup(provider.name(), &query_input, queued_at) {
+                        queued.set(Some((id, query_input)));
+                    }
+                    error.set(Some(err));
+                }
+                Err(err) => error.set(Some(err)),
+            }
+        });
+    };
+
+    // Barcodes aren't queued: an unknown code has nothing to back-fill.
+    let mut run_barcode = move |code: String| {
+        let provider = provider.read().clone();
+        last_query.set(code.clone());
+        error.set(None);
+        source.set(None);
+        queued.set(None);
+        stored.set(None);
+
+        spawn(async move {
+            match lookup_barcode(store, provider, &code).await {
+                Ok(BarcodeMatch::Stored(food)) => {
+                    foods.set(Vec::new());
+                    stored.set(Some(food));
+                }
+                Ok(BarcodeMatch::Provider(food)) => foods.set(review_rows(vec![food])),
+                Err(err) => error.set(Some(err)),
+            }
+        });
+    };
+
+    // Each call supersedes the last; only the newest search gets to finish.
+    let mut run_search = move |query_input: String| {
+        let generation = search_generation() + 1;
+        search_generation.set(generation);
+
+        spawn(async move {
+            tokio::time::sleep(SEARCH_DEBOUNCE).await;
+            if search_generation() != generation {
+                return;
+            }
+
+            let key = normalize_query(&query_input);
+            if key.chars().count() < MIN_SEARCH_CHARS {
+                hits.set(Vec::new());
+                return;
+            }
+            if let Some(found) = searched.read().get(&key).cloned() {
+                hits.set(found);
+                return;
+            }
+
+            let provider = provider.read().clone();
+            last_query.set(query_input.clone());
+            error.set(None);
+
+            match provider.search(&query_input).await {
+                Ok(found) => {
+                    if search_generation() == generation {
+                        hits.set(found.clone());
+                    }
+                    searched.write().insert(key, found);
+                }
+                // A newer search has taken over; its results aren't this error's to replace.
+                Err(err) if search_generation() == generation => error.set(Some(err)),
+                Err(_) => {}
+            }
+        });
+    };
+
+    // Full nutrients are only fetched for the candidate that was chosen.
+    let mut pick = move |hit: SearchHit| {
+        let provider = provider.read().clone();
+        error.set(None);
+        stored.set(None);
+        picked.set(Some(hit.id.clone()));
+
+        spawn(async move {
+            match provider.lookup(&hit.id).await {
+                Ok(food) => foods.set(review_rows(vec![food])),
+                Err(err) => {
+                    picked.set(None);
+                    error.set(Some(err));
+                }
+            }
+        });
+    };
+
+    let mut read_barcode_image = move |path: PathBuf| {
+        error_note.set(None);
+
+        spawn(async move {
+            match decode_file_in_background(path).await {
+                Ok(code) => {
+                    text.set(code.clone());
+                    run_barcode(code);
+                }
+                Err(err) => error_note.set(Some(err.to_string())),
+            }
+        });
+    };
+
+    let quota = describe_quota(provider.read().as_ref());
+
+    let error_banner = error
+        .read()
+        .as_ref()
+        .map(|err| (err.to_string(), err.is_retryable(), matches!(err, QueryError::Unauthorized(_))));
 
     rsx! {
         div {
-            input {
-                r#type: "text",
-                value: "{text}",
-                oninput: move |event| text.set(event.value()),
-                onkeydown: move |event| {
-                    if event.key() == Key::Enter {
-                        let query_input = text.clone().to_string();
-                        text.set(String::new());
-                        
-                        spawn(async move {
-                            let resp = query_nutritionx(query_input).await;
-
-                            match resp {
-                                Ok(data) => { 
-                                    foods.set(data.clone());
+            div { class: "add-mode",
+                button {
+                    disabled: mode() == AddMode::Describe,
+                    onclick: move |_| mode.set(AddMode::Describe),
+                    "Describe"
+                }
+                button {
+                    disabled: mode() == AddMode::Search,
+                    onclick: move |_| mode.set(AddMode::Search),
+                    "Search"
+                }
+                button {
+                    disabled: mode() == AddMode::Barcode,
+                    onclick: move |_| mode.set(AddMode::Barcode),
+                    "Barcode"
+                }
+            }
+
+            if mode() == AddMode::Describe {
+                input {
+                    r#type: "text",
+                    value: "{text}",
+                    oninput: move |event| text.set(event.value()),
+                    onkeydown: move |event| {
+                        if event.key() == Key::Enter {
+                            let query_input = text.clone().to_string();
+                            text.set(String::new());
+                            run_query(query_input, false);
+                        }
+                    }
+                }
+            } else if mode() == AddMode::Search {
+                input {
+                    r#type: "text",
+                    placeholder: "Start typing a food or product",
+                    value: "{text}",
+                    oninput: move |event| {
+                        text.set(event.value());
+                        run_search(event.value());
+                    },
+                }
+            } else {
+                input {
+                    r#type: "text",
+                    placeholder: "UPC or EAN",
+                    value: "{text}",
+                    oninput: move |event| text.set(event.value()),
+                    onkeydown: move |event| {
+                        if event.key() == Key::Enter {
+                            run_barcode(text());
+                        }
+                    }
+                }
+                input {
+                    r#type: "text",
+                    placeholder: "Or the path to a photo of the barcode",
+                    value: "{image_path}",
+                    oninput: move |event| image_path.set(event.value()),
+                }
+                button {
+                    onclick: move |_| {
+                        let path = image_path.read().trim().to_string();
+                        if !path.is_empty() {
+                            read_barcode_image(PathBuf::from(path));
+                        }
+                    },
+                    "Read barcode"
+                }
+            }
+
+            if let Some(quota) = quota {
+                p { class: "quota", "{quota}" }
+            }
+
+            if let Some((message, retryable, unauthorized)) = error_banner {
+                div { class: "error-banner",
+                    span { "{message}" }
+                    if retryable {
+                        button {
+                            onclick: move |_| match mode() {
+                                AddMode::Describe => run_query(last_query(), false),
+                                AddMode::Search => run_search(last_query()),
+                                AddMode::Barcode => run_barcode(last_query()),
+                            },
+                            "Retry"
+                        }
+                    }
+                    if unauthorized {
+                        Link { to: Route::Settings {}, "Check credentials in Settings" }
+                    }
+                    button {
+                        onclick: move |_| error.set(None),
+                        "Dismiss"
+                    }
+                }
+            }
+
+            if let Some((id, query)) = queued() {
+                div { class: "queued-notice",
+                    span { "\"{query}\" is queued and will be looked up automatically." }
+                    if logged() {
+                        span { " A placeholder entry was logged for it." }
+                    } else {
+                        button {
+                            onclick: move |_| {
+                                match store.write().log_pending_entry(id, Utc::now().naive_utc()) {
+                                    Ok(()) => logged.set(true),
+                                    Err(err) => error_note.set(Some(err.to_string())),
+                                }
+                            },
+                            "Log it as an entry now"
+                        }
+                    }
+                }
+            }
+
+            if let Some(note) = error_note() {
+                p { class: "error", "{note}" }
+            }
+
+            if mode() == AddMode::Search {
+                for (heading, group) in group_hits(&hits.read()) {
+                    h4 { "{heading}" }
+                    ul { class: "search-hits",
+                        for hit in group {
+                            li { key: "{hit.id}",
+                                button {
+                                    disabled: picked() == Some(hit.id.clone()),
+                                    onclick: {
+                                        let hit = hit.clone();
+                                        move |_| pick(hit.clone())
+                                    },
+                                    {describe_hit(&hit)}
                                 }
-                                Err(err) => {}
                             }
-                        });
+                        }
+                    }
+                }
+            }
+
+            if let Some(food) = stored() {
+                p { class: "barcode-match", {describe_stored_match(&food)} }
+            }
+
+            if let Some((lookup_source, fetched_at)) = source() {
+                if lookup_source != LookupSource::Provider {
+                    div { class: "cache-notice",
+                        if lookup_source == LookupSource::StaleCache {
+                            span { "Provider unreachable; showing an expired cached result from {fetched_at}" }
+                        } else {
+                            span { "From cache, fetched {fetched_at}" }
+                        }
+                        button {
+                            onclick: move |_| run_query(last_query(), true),
+                            "Refresh"
+                        }
                     }
                 }
             }
 
             br {} br {}
 
-            if foods.len() > 0 {
-                button {
-                    onclick: move |_| {
-                        add_food_items(((*foods))().clone());
-                        foods.set(Vec::new());
+            ReviewFoods { rows: foods }
+
+            PendingLookups {}
+        }
+    }
+}
+
+// A looked-up food as it will be stored, editable until it's added.
+#[derive(Debug, Clone, PartialEq)]
+struct ReviewRow {
+    food: ProviderFood,
+    include: bool,
+}
+
+fn review_rows(foods: Vec<ProviderFood>) -> Vec<ReviewRow> {
+    foods.into_iter().map(|food| ReviewRow { food, include: true }).collect()
+}
+
+// A corrected serving weight is still the same food, so the amounts shown
+// for the serving are scaled with it. A weight of 0 g or less would lose
+// them, so it is ignored; amounts the source gave no weight for are taken
+// to be for the one entered.
+fn resize_serving(food: &mut ProviderFood, grams: f32) {
+    if !grams.is_finite() || grams <= 0.0 {
+        return;
+    }
+    if food.serving_weight_grams > 0.0 {
+        let scale = grams / food.serving_weight_grams;
+        for amount in food.nutrients.values_mut().chain(food.other_nutrients.values_mut()) {
+            *amount *= scale;
+        }
+    }
+    food.serving_weight_grams = grams;
+}
+
+// Catches what the database would accept but is clearly a mistake.
+fn check_review(food: &ProviderFood) -> Result<(), String> {
+    if food.name.trim().is_empty() {
+        return Err("A name is required".to_string());
+    }
+    if !food.serving_weight_grams.is_finite() || food.serving_weight_grams <= 0.0 {
+        return Err("The serving weight must be more than 0 g".to_string());
+    }
+    if let Some((kind, _)) = food.nutrients.iter().find(|(_, value)| !value.is_finite() || **value < 0.0) {
+        return Err(format!("{} must be 0 or more", kind));
+    }
+    Ok(())
+}
+
+#[component]
+fn ReviewFoods(rows: Signal<Vec<ReviewRow>>) -> Element {
+    let mut store = use_context::<Store>();
+    let on_conflict = use_signal(ConflictStrategy::default);
+    // Name and outcome of each food in the last add.
+    let mut report = use_signal(Vec::<(String, Result<AddOutcome, String>)>::new);
+
+    // Rows that were added leave the table; failed ones stay to be fixed.
+    let mut add_selected = move || {
+        let included: Vec<(usize, ProviderFood)> = rows
+            .read()
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.include)
+            .map(|(index, row)| (index, row.food.clone()))
+            .collect();
+
+        let mut added = Vec::new();
+        let mut outcomes = Vec::new();
+        for (index, mut food) in included {
+            food.name = food.name.trim().to_string();
+            let name = food.name.clone();
+            let result = check_review(&food)
+                .and_then(|()| store.write().add_food(food, on_conflict()).map_err(|err| err.to_string()));
+
+            // Skipped rows stay so they can be renamed and tried again.
+            if matches!(&result, Ok(outcome) if *outcome != AddOutcome::Skipped) {
+                added.push(index);
+            }
+            outcomes.push((name, result));
+        }
+
+        let mut index = 0;
+        rows.write().retain(|_| {
+            index += 1;
+            !added.contains(&(index - 1))
+        });
+        report.set(outcomes);
+    };
+
+    let selected = rows.read().iter().filter(|row| row.include).count();
+
+    rsx! {
+        if !rows.read().is_empty() {
+            table { class: "review",
+                tr {
+                    th { "Add" }
+                    th { "Name" }
+                    th { "Serving (g)" }
+                    th { "Nutrients per serving" }
+                }
+                for (index, row) in rows().into_iter().enumerate() {
+                    tr {
+                        td {
+                            input {
+                                r#type: "checkbox",
+                                checked: row.include,
+                                onchange: move |event| rows.write()[index].include = event.checked(),
+                            }
+                        }
+                        td {
+                            input {
+                                r#type: "text",
+                                value: "{row.food.name}",
+                                oninput: move |event| rows.write()[index].food.name = event.value(),
+                            }
+                            if let Some(brand) = &row.food.brand {
+                                span { class: "barcode", " {brand}" }
+                            }
+                        }
+                        td {
+                            input {
+                                r#type: "number",
+                                min: "0",
+                                step: "any",
+                                value: "{row.food.serving_weight_grams}",
+                                oninput: move |event| {
+                                    if let Ok(grams) = event.value().parse::<f32>() {
+                                        resize_serving(&mut rows.write()[index].food, grams);
+                                    }
+                                },
+                            }
+                        }
+                        td {
+                            details {
+                                summary { "{row.food.nutrients.len()} nutrients" }
+                                table {
+                                    for (kind, value) in row.food.nutrients.iter().collect::<BTreeMap<_, _>>() {
+                                        tr {
+                                            td { "{kind}" }
+                                            td {
+                                                input {
+                                                    r#type: "number",
+                                                    min: "0",
+                                                    step: "any",
+                                                    value: "{value}",
+                                                    oninput: {
+                                                        let kind = *kind;
+                                                        move |event: FormEvent| {
+                                                            if let Ok(amount) = event.value().parse::<f32>() {
+                                                                rows.write()[index].food.nutrients.insert(kind, amount);
+                                                            }
+                                                        }
+                                                    },
+                                                }
+                                            }
+                                            td { "{kind.unit()}" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            button {
+                disabled: selected == 0,
+                onclick: move |_| add_selected(),
+                "Add {selected} to Database"
+            }
+            button {
+                onclick: move |_| rows.set(Vec::new()),
+                "Discard"
+            }
+            ConflictChoice { choice: on_conflict }
+        }
+
+        if !report.read().is_empty() {
+            ul { class: "add-report",
+                for (name, result) in report() {
+                    match result {
+                        Ok(AddOutcome::Skipped) => rsx! { li { class: "error", {describe_outcome(&name, &AddOutcome::Skipped)} } },
+                        Ok(outcome) => rsx! { li { class: "added", {describe_outcome(&name, &outcome)} } },
+                        Err(err) => rsx! { li { class: "error", "{name}: {err}" } },
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn CustomFood() -> Element {
+    let mut store = use_context::<Store>();
+    let mut name = use_signal(|| String::new());
+    let mut brand = use_signal(|| String::new());
+    let mut serving_label = use_signal(|| String::new());
+    let mut serving_grams = use_signal(|| String::new());
+    let mut basis = use_signal(LabelBasis::default);
+    // What was typed for each label nutrient, parsed on save.
+    let mut values = use_signal(BTreeMap::<NutrientKind, String>::new);
+    let on_conflict = use_signal(ConflictStrategy::default);
+    let mut error = use_signal(|| None::<String>);
+    // Shown until the user confirms the label really says that.
+    let mut warnings = use_signal(Vec::<String>::new);
+    let mut message = use_signal(|| None::<String>);
+
+    let mut save = move |confirmed: bool| {
+        message.set(None);
+        let label = match read_label(&name(), &brand(), &serving_label(), &serving_grams(), basis(), &values()) {
+            Ok(label) => label,
+            Err(err) => {
+                error.set(Some(err));
+                return;
+            }
+        };
+        if let Err(err) = label.validate() {
+            error.set(Some(err.to_string()));
+            return;
+        }
+        error.set(None);
+
+        let found = label.warnings();
+        if !confirmed && !found.is_empty() {
+            warnings.set(found);
+            return;
+        }
+        warnings.set(Vec::new());
+
+        let food = label.to_food();
+        let food_name = food.name.clone();
+        let result = store.write().add_food(food, on_conflict());
+        match result {
+            Ok(AddOutcome::Skipped) => error.set(Some(describe_outcome(&food_name, &AddOutcome::Skipped))),
+            Ok(outcome) => {
+                message.set(Some(describe_outcome(&food_name, &outcome)));
+                name.set(String::new());
+                brand.set(String::new());
+                serving_label.set(String::new());
+                serving_grams.set(String::new());
+                values.set(BTreeMap::new());
+            }
+            Err(err) => error.set(Some(err.to_string())),
+        }
+    };
+
+    rsx! {
+        h2 { "Custom food" }
+        p { "Copy the values from a nutrition facts label. Leave out anything the label doesn't list." }
+
+        div { class: "food-detail",
+            label { "Name "
+                input { r#type: "text", value: "{name}", oninput: move |event| name.set(event.value()) }
+            }
+            label { "Brand "
+                input { r#type: "text", value: "{brand}", oninput: move |event| brand.set(event.value()) }
+            }
+            label { "Serving size "
+                input {
+                    r#type: "text",
+                    placeholder: "e.g. 2 tbsp",
+                    value: "{serving_label}",
+                    oninput: move |event| serving_label.set(event.value()),
+                }
+                input {
+                    r#type: "number",
+                    min: "0",
+                    step: "any",
+                    value: "{serving_grams}",
+                    oninput: move |event| serving_grams.set(event.value()),
+                }
+                " g"
+            }
+            label { "Values are "
+                select {
+                    onchange: move |event| {
+                        if let Some(chosen) = LabelBasis::all().iter().find(|chosen| chosen.label() == event.value()) {
+                            basis.set(*chosen);
+                        }
                     },
-                    "Add to Database"
+                    for choice in LabelBasis::all() {
+                        option {
+                            value: "{choice.label()}",
+                            selected: *choice == basis(),
+                            "{choice.label()}"
+                        }
+                    }
                 }
             }
 
-            for food in foods_lock.iter() {
-                { let food: &ApiFood = food; }
-                h3 { "{food.food_name} {food.serving_weight_grams}" }
-                ul {
-                    for nutrient in &food.full_nutrients {
-                        if let Some(nutrient_name) = nutrient_name_map.get(&nutrient.attr_id) {
-                            li { "{nutrient_name}: {nutrient.value}" }
+            table {
+                for kind in LABEL_NUTRIENTS.iter().copied() {
+                    tr {
+                        td { "{kind}" }
+                        td {
+                            input {
+                                r#type: "number",
+                                min: "0",
+                                step: "any",
+                                value: "{values.read().get(&kind).cloned().unwrap_or_default()}",
+                                oninput: move |event| {
+                                    values.write().insert(kind, event.value());
+                                },
+                            }
                         }
+                        td { "{kind.unit()}" }
+                    }
+                }
+            }
+
+            ConflictChoice { choice: on_conflict }
+            button { onclick: move |_| save(false), "Save food" }
+        }
+
+        if let Some(error) = error() {
+            p { class: "error", "{error}" }
+        }
+        if !warnings.read().is_empty() {
+            div { class: "error-banner",
+                for warning in warnings() {
+                    p { "{warning}" }
+                }
+                button { onclick: move |_| save(true), "Save anyway" }
+                button { onclick: move |_| warnings.set(Vec::new()), "Let me check" }
+            }
+        }
+        if let Some(message) = message() {
+            p { "{message}" }
+        }
+    }
+}
+
+// Turns the form's text into a label; blank fields are simply not on it.
+fn read_label(
+    name: &str,
+    brand: &str,
+    serving_label: &str,
+    serving_grams: &str,
+    basis: LabelBasis,
+    values: &BTreeMap<NutrientKind, String>,
+) -> Result<NutritionLabel, String> {
+    let optional_text = |text: &str| Some(text.trim().to_string()).filter(|text| !text.is_empty());
+    let optional_number = |what: &str, text: &str| match text.trim() {
+        "" => Ok(None),
+        number => number.parse::<f32>().map(Some).map_err(|_| format!("{} isn't a number", what)),
+    };
+
+    let mut label = NutritionLabel {
+        name: name.trim().to_string(),
+        brand: optional_text(brand),
+        serving_label: optional_text(serving_label),
+        serving_grams: optional_number("The serving size", serving_grams)?,
+        basis,
+        values: BTreeMap::new(),
+    };
+    for (kind, text) in values {
+        if let Some(value) = optional_number(kind.name(), text)? {
+            label.values.insert(*kind, value);
+        }
+    }
+
+    Ok(label)
+}
+
+fn describe_outcome(name: &str, outcome: &AddOutcome) -> String {
+    match outcome {
+        AddOutcome::Added => format!("Added {}", name),
+        AddOutcome::Renamed(stored_as) => format!("Added {} as {}", name, stored_as),
+        AddOutcome::Skipped => format!("Skipped {}: a food with that name is already stored", name),
+        AddOutcome::Overwritten => format!("Overwrote the stored {}", name),
+        AddOutcome::Merged => format!("Merged into the stored {}", name),
+    }
+}
+
+fn describe_pending(lookup: &PendingLookup) -> String {
+    let mut description = format!("{} (queued {} UTC)", lookup.query, lookup.queued_at.format("%Y-%m-%d %H:%M"));
+    if let Some(timestamp) = lookup.entry_timestamp {
+        description.push_str(&format!(", for the entry at {} UTC", timestamp.format("%Y-%m-%d %H:%M")));
+    }
+    description
+}
+
+// Common foods first, then branded products, leaving out empty groups.
+fn group_hits(hits: &[SearchHit]) -> Vec<(&'static str, Vec<SearchHit>)> {
+    let (branded, common): (Vec<SearchHit>, Vec<SearchHit>) = hits.iter().cloned().partition(|hit| hit.branded);
+
+    [("Common foods", common), ("Branded products", branded)]
+        .into_iter()
+        .filter(|(_, group)| !group.is_empty())
+        .collect()
+}
+
+fn describe_hit(hit: &SearchHit) -> String {
+    let mut description = hit.name.clone();
+    if let Some(brand) = &hit.brand {
+        description.push_str(&format!(" ({})", brand));
+    }
+    if let Some(serving) = &hit.serving {
+        description.push_str(&format!(", {}", serving));
+    }
+    if let Some(calories) = hit.calories {
+        description.push_str(&format!(", {} kcal", calories));
+    }
+    description
+}
+
+fn describe_stored_match(food: &Food) -> String {
+    match &food.brand {
+        Some(brand) => format!("Already in your foods as {} ({}).", food.name, brand),
+        None => format!("Already in your foods as {}.", food.name),
+    }
+}
+
+fn describe_quota(provider: &dyn NutritionProvider) -> Option<String> {
+    provider
+        .quota()
+        .map(|usage| format!("{}: {} of {} requests used today", provider.name(), usage.used, usage.limit))
+}
+
+#[component]
+fn PendingLookups() -> Element {
+    let mut store = use_context::<Store>();
+    let provider = use_context::<Provider>();
+    let pending = store.read().pending_lookups().unwrap_or_default();
+
+    if pending.is_empty() {
+        return rsx! {};
+    }
+
+    rsx! {
+        h3 { "Waiting to be looked up" }
+        ul {
+            for lookup in pending {
+                li { key: "{lookup.id}",
+                    {describe_pending(&lookup)}
+                    if let Some(last_error) = &lookup.last_error {
+                        span { class: "error", " {last_error}" }
                     }
-                } 
+                    button {
+                        onclick: {
+                            let lookup = lookup.clone();
+                            move |_| {
+                                let lookup = lookup.clone();
+                                let current = provider.read().clone();
+                                spawn(async move {
+                                    // Failures are recorded on the lookup and shown here.
+                                    let _ = resolve_pending(store, current, &lookup).await;
+                                });
+                            }
+                        },
+                        "Retry"
+                    }
+                    button {
+                        onclick: move |_| {
+                            let _ = store.write().discard_lookup(lookup.id);
+                        },
+                        "Discard"
+                    }
+                }
             }
         }
     }
@@ -233,38 +1492,95 @@ fn AddRecipe() -> Element {
     */
 }
 
+// Nutrients shown for what's left of each pantry item.
+const PANTRY_NUTRIENTS: &[NutrientKind] = &[
+    NutrientKind::Energy,
+    NutrientKind::Protein,
+    NutrientKind::TotalFat,
+    NutrientKind::Carbohydrate,
+];
+
 #[component]
 fn AddPantryItem() -> Element {
-    let mut foods = use_signal(|| Vec::new());
-    let mut index = use_signal(|| 0);
+    let mut store = use_context::<Store>();
+    let selected = use_signal(|| String::new());
+    let mut amount = use_signal(|| String::new());
+    let mut message = use_signal(|| None::<String>);
 
-    use_effect(move || {
-        foods.set(get_foods().expect(""));
-    });
+    let pantry = store.read().pantry().unwrap_or_default();
+    let chosen = store.read().food_named(&selected()).ok().flatten();
+    let grams = chosen
+        .filter(|_| !amount.read().trim().is_empty())
+        .map(|chosen| to_grams(&chosen, &amount()));
 
-    rsx! {
-        select {
-            onchange: move |event| { index.set(event.value().parse().expect("")); },
-            { foods.iter().enumerate().map(|(i, food)| {
-                rsx! { option { value: i as f64, "{food.name}" }, }
-            }) }
-        }
+    let ready = grams.as_ref().and_then(|grams| grams.as_ref().ok()).copied();
 
-        br{}
+    let add = move |_| {
+        let Some(weight_grams) = ready else {
+            return;
+        };
+        let result = store.write().add_pantry_item(&selected(), weight_grams);
+        match result {
+            Ok(()) => {
+                message.set(Some(format!("Added {} of {} to the pantry.", amount(), selected())));
+                amount.set(String::new());
+            }
+            Err(err) => message.set(Some(err.to_string())),
+        }
+    };
 
-        {
-            foods.with(|foods| {
-                if let Some(food) = foods.get(index()) {
-                    format!("Selected food: {}", food.name)
-                } else {
-                    "No food selected".to_string()
-                }
-            })
+    rsx! {
+        h2 { "Pantry" }
+        if pantry.is_empty() {
+            p { "Nothing in the pantry yet." }
+        }
+        ul {
+            for item in pantry {
+                li { {describe_pantry_item(&item)} }
+            }
         }
 
+        h3 { "Add to the pantry" }
+        FoodPicker { selected }
+        input {
+            r#type: "text",
+            placeholder: "e.g. 1 bag, 2 cups or 500 g",
+            value: "{amount}",
+            oninput: move |event| amount.set(event.value()),
+        }
+        button {
+            disabled: ready.is_none(),
+            onclick: add,
+            "Add"
+        }
+        match &grams {
+            Some(Ok(grams)) => rsx! { span { class: "hint", " {grams:.0} g" } },
+            Some(Err(err)) => rsx! { span { class: "hint", " {err}" } },
+            None => rsx! {},
+        }
+        if let Some(message) = message() {
+            p { "{message}" }
+        }
     }
 }
 
+// "rice: 350 of 500 g left; Energy: 455.00 kcal, ..." with the nutrients
+// scaled to what's left.
+fn describe_pantry_item(item: &PantryItem) -> String {
+    let remaining = item.remaining_nutrients();
+    let nutrients: Vec<String> = PANTRY_NUTRIENTS
+        .iter()
+        .map(|kind| format!("{}: {}", kind, describe_amount(remaining.get(kind).copied().flatten(), *kind)))
+        .collect();
+    format!(
+        "{}: {:.0} of {:.0} g left; {}",
+        item.food.name,
+        item.weight_grams_remaining,
+        item.weight_grams,
+        nutrients.join(", ")
+    )
+}
+
 
 
 
@@ -277,17 +1593,341 @@ fn AddPantryItem() -> Element {
 
 #[component]
 fn Foods() -> Element {
+    let store = use_context::<Store>();
     let mut foods = use_signal(|| Vec::new());
+    let mut load_error = use_signal(|| None::<String>);
 
     use_effect(move || {
-        foods.set(get_foods().expect(""));
+        match store.read().foods() {
+            Ok(stored) => {
+                foods.set(stored);
+                load_error.set(None);
+            }
+            Err(err) => load_error.set(Some(err.to_string())),
+        }
     });
 
     rsx! {
+        if let Some(err) = load_error() {
+            p { class: "error", "{err}" }
+        }
         for food in foods.iter() {
-            p { "{food.name}" }
+            p {
+                Link { to: Route::FoodDetail { name: food.name.clone() }, "{food.name}" }
+                if let Some(brand) = &food.brand {
+                    " ({brand})"
+                }
+                if let Some(barcode) = &food.barcode {
+                    span { class: "barcode", " {barcode}" }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn FoodDetail(name: String) -> Element {
+    let store = use_context::<Store>();
+    let stored = store.read().food_named(&name);
+
+    match stored {
+        // Keyed so the editor starts over when the route moves to another food.
+        Ok(Some(food)) => rsx! { FoodEditor { key: "{name}", food } },
+        Ok(None) => rsx! { p { "No food named \"{name}\"." } },
+        Err(err) => rsx! { p { class: "error", "{err}" } },
+    }
+}
+
+#[component]
+fn FoodEditor(food: Food) -> Element {
+    let mut store = use_context::<Store>();
+    let navigator = use_navigator();
+    let stored_name = use_signal(|| food.name.clone());
+    let mut original = use_signal(|| food.clone());
+    let mut draft = use_signal(|| food.clone());
+    // Kept as typed, so a trailing comma survives until Save splits it up.
+    let mut aliases = use_signal(|| food.aliases.join(", "));
+    let mut message = use_signal(|| None::<String>);
+    // Set once Delete finds the food in use, to offer what to do about it.
+    let mut in_use = use_signal(|| None::<FoodReferences>);
+    let replacement = use_signal(|| String::new());
+
+    let has_others = store
+        .read()
+        .foods()
+        .unwrap_or_default()
+        .iter()
+        .any(|other| other.name != stored_name());
+
+    let save = move |_| {
+        let mut edited = draft();
+        edited.aliases = aliases
+            .read()
+            .split(',')
+            .map(|alias| alias.trim().to_string())
+            .filter(|alias| !alias.is_empty())
+            .collect();
+        for measure in &mut edited.measures {
+            measure.name = measure.name.trim().to_string();
+        }
+        if let Err(err) = check_food(&edited) {
+            message.set(Some(err));
+            return;
+        }
+
+        let result = store.write().update_food(&stored_name(), &edited);
+        match result {
+            Ok(()) if edited.name != stored_name() => {
+                navigator.replace(Route::FoodDetail { name: edited.name });
+            }
+            Ok(()) => {
+                original.set(edited);
+                message.set(Some("Saved.".to_string()));
+            }
+            Err(err) => message.set(Some(err.to_string())),
+        }
+    };
+
+    let mut delete = move |references: ReferenceHandling| {
+        let result = store.write().delete_food(&stored_name(), references);
+        match result {
+            Ok(()) => {
+                navigator.replace(Route::Foods {});
+            }
+            Err(StorageError::InUse { references, .. }) => in_use.set(Some(references)),
+            Err(err) => message.set(Some(err.to_string())),
+        }
+    };
+
+    let current = draft();
+    let per_serving = current.serving_grams.map(|grams| current.scaled_to(grams));
+
+    rsx! {
+        h2 { "{stored_name}" }
+        Link { to: Route::Foods {}, "Back to foods" }
+
+        div { class: "food-detail",
+            label { "Name "
+                input {
+                    r#type: "text",
+                    value: "{current.name}",
+                    oninput: move |event| draft.write().name = event.value(),
+                }
+            }
+            label { "Brand "
+                input {
+                    r#type: "text",
+                    value: "{current.brand.clone().unwrap_or_default()}",
+                    oninput: move |event| {
+                        draft.write().brand = Some(event.value().trim().to_string()).filter(|brand| !brand.is_empty());
+                    },
+                }
+            }
+            label { "Also known as "
+                input {
+                    r#type: "text",
+                    placeholder: "e.g. garbanzo beans, chana",
+                    value: "{aliases}",
+                    oninput: move |event| aliases.set(event.value()),
+                }
+            }
+            label { "Serving "
+                input {
+                    r#type: "text",
+                    placeholder: "e.g. 1 cup",
+                    value: "{current.serving_label.clone().unwrap_or_default()}",
+                    oninput: move |event| {
+                        draft.write().serving_label = Some(event.value().trim().to_string()).filter(|label| !label.is_empty());
+                    },
+                }
+                input {
+                    r#type: "number",
+                    min: "0",
+                    step: "any",
+                    value: "{current.serving_grams.map(|grams| grams.to_string()).unwrap_or_default()}",
+                    oninput: move |event| {
+                        // Clearing the field leaves the food without a serving.
+                        let text = event.value();
+                        if text.trim().is_empty() {
+                            draft.write().serving_grams = None;
+                        } else if let Ok(grams) = text.trim().parse::<f32>() {
+                            draft.write().serving_grams = Some(grams);
+                        }
+                    },
+                }
+                " g"
+            }
+            if let Some(barcode) = &current.barcode {
+                p { class: "barcode", "Barcode {barcode}" }
+            }
+
+            h4 { "Measures" }
+            p { class: "hint", "Used to enter amounts like \"2 slices\". An \"ml\" measure sets the grams per ml for volumes." }
+            table { class: "measures",
+                for (index, measure) in current.measures.iter().enumerate() {
+                    tr {
+                        td { "1 "
+                            input {
+                                r#type: "text",
+                                value: "{measure.name}",
+                                oninput: move |event| draft.write().measures[index].name = event.value().to_lowercase(),
+                            }
+                        }
+                        td { "= "
+                            input {
+                                r#type: "number",
+                                min: "0",
+                                step: "any",
+                                value: "{measure.grams}",
+                                oninput: move |event| {
+                                    if let Ok(grams) = event.value().parse::<f32>() {
+                                        draft.write().measures[index].grams = grams;
+                                    }
+                                },
+                            }
+                            " g"
+                        }
+                        td {
+                            button {
+                                onclick: move |_| {
+                                    draft.write().measures.remove(index);
+                                },
+                                "Remove"
+                            }
+                        }
+                    }
+                }
+            }
+            button {
+                onclick: move |_| draft.write().measures.push(Measure { name: String::new(), grams: 0.0 }),
+                "Add a measure"
+            }
+
+            for (category, nutrients) in nutrients_by_category(&current) {
+                h4 { "{category.label()}" }
+                table {
+                    tr {
+                        th {}
+                        th { "Per {BASIS_GRAMS} g" }
+                        th {}
+                        if per_serving.is_some() {
+                            th { "Per serving" }
+                        }
+                    }
+                    for (kind, value) in nutrients {
+                        tr {
+                            td { "{kind}" }
+                            td {
+                                input {
+                                    r#type: "number",
+                                    min: "0",
+                                    step: "any",
+                                    placeholder: "not reported",
+                                    value: "{value.map(|value| value.to_string()).unwrap_or_default()}",
+                                    oninput: move |event| {
+                                        // Clearing the field marks the nutrient as not reported.
+                                        let text = event.value();
+                                        if text.trim().is_empty() {
+                                            draft.write().nutrients.insert(kind, None);
+                                        } else if let Ok(amount) = text.trim().parse::<f32>() {
+                                            draft.write().nutrients.insert(kind, Some(amount));
+                                        }
+                                    },
+                                }
+                            }
+                            td { "{kind.unit()}" }
+                            if let Some(per_serving) = &per_serving {
+                                td { {describe_amount(per_serving.get(&kind).copied().flatten(), kind)} }
+                            }
+                        }
+                    }
+                }
+            }
+
+            button { onclick: save, "Save" }
+            button {
+                onclick: move |_| {
+                    aliases.set(original().aliases.join(", "));
+                    draft.set(original());
+                },
+                "Undo changes"
+            }
+            button {
+                onclick: move |_| delete(ReferenceHandling::Refuse),
+                "Delete"
+            }
+        }
+
+        if let Some(message) = message() {
+            p { "{message}" }
+        }
+
+        if let Some(references) = in_use() {
+            div { class: "error-banner",
+                p { "{stored_name} is used by {references}." }
+                button {
+                    onclick: move |_| delete(ReferenceHandling::Cascade),
+                    "Delete it from those too"
+                }
+                if has_others {
+                    FoodPicker { selected: replacement, exclude: stored_name() }
+                    button {
+                        disabled: replacement.read().is_empty(),
+                        onclick: move |_| delete(ReferenceHandling::ReassignTo(replacement())),
+                        "Use the replacement instead"
+                    }
+                }
+                button {
+                    onclick: move |_| in_use.set(None),
+                    "Cancel"
+                }
+            }
+        }
+    }
+}
+
+// Every registry nutrient, grouped for the detail view, with None where the
+// food has no value.
+fn nutrients_by_category(food: &Food) -> BTreeMap<NutrientCategory, Vec<(NutrientKind, Option<f32>)>> {
+    let mut grouped: BTreeMap<NutrientCategory, Vec<(NutrientKind, Option<f32>)>> = BTreeMap::new();
+
+    for kind in NutrientKind::all() {
+        grouped.entry(kind.category()).or_default().push((kind, food.nutrient(kind)));
+    }
+
+    grouped
+}
+
+fn check_food(food: &Food) -> Result<(), String> {
+    if food.name.trim().is_empty() {
+        return Err("A name is required".to_string());
+    }
+    if matches!(food.serving_grams, Some(grams) if !grams.is_finite() || grams <= 0.0) {
+        return Err("The serving must be more than 0 g".to_string());
+    }
+    let invalid = |amount: &Option<f32>| matches!(amount, Some(amount) if !amount.is_finite() || *amount < 0.0);
+    if let Some((kind, _)) = food.nutrients.iter().find(|(_, amount)| invalid(amount)) {
+        return Err(format!("{} must be 0 or more", kind));
+    }
+    for (index, measure) in food.measures.iter().enumerate() {
+        if measure.name.is_empty() {
+            return Err("Every measure needs a name".to_string());
+        }
+        if !measure.grams.is_finite() || measure.grams <= 0.0 {
+            return Err(format!("A {} must weigh more than 0 g", measure.name));
+        }
+        if food.measures[..index].iter().any(|other| other.name == measure.name) {
+            return Err(format!("There are two {} measures", measure.name));
         }
     }
+    Ok(())
+}
+
+fn describe_amount(amount: Option<f32>, kind: NutrientKind) -> String {
+    match amount {
+        Some(amount) => format!("{:.2} {}", amount, kind.unit()),
+        None => "not reported".to_string(),
+    }
 }
 
 #[component]
@@ -0,0 +1,219 @@
+// Household measures such as "2 slices" or "1/2 cup", and the conversions
+// that turn them into the grams every portion is stored in. Weights convert
+// directly; volumes need the food's density, which comes from a measure
+// named "ml" or from any measure that is itself a volume, like "cup".
+
+use crate::data_entry::Food;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Measure {
+    // Lowercase, e.g. "slice", "cup" or "large".
+    pub name: String,
+    // Weight of one of it.
+    pub grams: f32,
+}
+
+impl Measure {
+    // `quantity` of `name` weighing `grams`, as sources describe a serving.
+    // Weights like "oz" are left out, since they convert without the food.
+    pub fn new(name: &str, quantity: f32, grams: f32) -> Option<Measure> {
+        let name = name.trim().to_lowercase();
+        let valid = quantity > 0.0 && grams > 0.0 && grams.is_finite() && !name.is_empty();
+        if !valid || matches!(standard_unit(&name), Some(StandardUnit::Mass(_))) {
+            return None;
+        }
+        Some(Measure { name, grams: grams / quantity })
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MeasureError {
+    #[error("\"{0}\" doesn't start with an amount")]
+    NoAmount(String),
+    #[error("The amount must be more than 0")]
+    NotPositive,
+    #[error("{food} has no serving size")]
+    NoServing { food: String },
+    #[error("{food} has no \"{measure}\" measure; add one on its page")]
+    UnknownMeasure { food: String, measure: String },
+    #[error("{food} needs a density to be measured in {measure}; add an \"ml\" measure with its grams per ml")]
+    NeedsDensity { food: String, measure: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StandardUnit {
+    // Grams in one.
+    Mass(f32),
+    // Millilitres in one (US customary for the kitchen measures).
+    Volume(f32),
+}
+
+const STANDARD_UNITS: &[(&[&str], StandardUnit)] = &[
+    (&["g", "gram", "grams"], StandardUnit::Mass(1.0)),
+    (&["kg", "kilogram", "kilograms"], StandardUnit::Mass(1_000.0)),
+    (&["oz", "ounce", "ounces"], StandardUnit::Mass(28.349_523)),
+    (&["lb", "lbs", "pound", "pounds"], StandardUnit::Mass(453.592_37)),
+    (&["ml", "milliliter", "milliliters", "millilitre", "millilitres"], StandardUnit::Volume(1.0)),
+    (&["l", "liter", "liters", "litre", "litres"], StandardUnit::Volume(1_000.0)),
+    (&["tsp", "teaspoon", "teaspoons"], StandardUnit::Volume(4.928_922)),
+    (&["tbsp", "tablespoon", "tablespoons"], StandardUnit::Volume(14.786_765)),
+    (&["fl oz", "fluid ounce", "fluid ounces"], StandardUnit::Volume(29.573_53)),
+    (&["cup", "cups"], StandardUnit::Volume(236.588_24)),
+];
+
+const SERVING_NAMES: &[&str] = &["serving", "servings"];
+
+fn standard_unit(name: &str) -> Option<StandardUnit> {
+    STANDARD_UNITS
+        .iter()
+        .find(|(names, _)| names.contains(&name))
+        .map(|(_, unit)| *unit)
+}
+
+// "slices" and "slice" are the same measure.
+fn same_measure(a: &str, b: &str) -> bool {
+    let singular = |name: &str| name.strip_suffix('s').filter(|stem| !stem.ends_with('s')).unwrap_or(name).to_string();
+    a == b || singular(a) == singular(b)
+}
+
+// Splits "1 1/2 cups" into 1.5 and "cups". Whole numbers, decimals, fractions
+// and mixed numbers are understood; the unit is lowercased and may be empty.
+pub fn parse_amount(text: &str) -> Result<(f32, String), MeasureError> {
+    let text = text.trim().to_lowercase();
+    let mut amount: Option<f32> = None;
+    let mut rest = text.as_str();
+
+    loop {
+        let end = rest.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '/')).unwrap_or(rest.len());
+        let number = match parse_number(&rest[..end]) {
+            Some(number) => number,
+            None => break,
+        };
+        // Only a fraction may follow a whole number, as in "1 1/2".
+        let fraction = rest[..end].contains('/');
+        match amount {
+            None => amount = Some(number),
+            Some(whole) if fraction && whole.fract() == 0.0 => amount = Some(whole + number),
+            Some(_) => break,
+        }
+        rest = rest[end..].trim_start();
+        if fraction {
+            break;
+        }
+    }
+
+    match amount {
+        None => Err(MeasureError::NoAmount(text.to_string())),
+        Some(amount) if !amount.is_finite() || amount <= 0.0 => Err(MeasureError::NotPositive),
+        Some(amount) => Ok((amount, rest.trim().to_string())),
+    }
+}
+
+fn parse_number(text: &str) -> Option<f32> {
+    match text.split_once('/') {
+        Some((numerator, denominator)) => {
+            let denominator: f32 = denominator.parse().ok()?;
+            (denominator != 0.0).then_some(numerator.parse::<f32>().ok()? / denominator)
+        }
+        None => text.parse().ok(),
+    }
+}
+
+// Grams in `amount` of `food`, e.g. "2 slices", "1/2 cup" or "150 g". A bare
+// number is grams, as weights were always entered before.
+pub fn to_grams(food: &Food, amount: &str) -> Result<f32, MeasureError> {
+    let (quantity, unit) = parse_amount(amount)?;
+    Ok(quantity * grams_per(food, &unit)?)
+}
+
+fn grams_per(food: &Food, unit: &str) -> Result<f32, MeasureError> {
+    if unit.is_empty() {
+        return Ok(1.0);
+    }
+    if SERVING_NAMES.contains(&unit) {
+        return food.serving_grams.ok_or_else(|| MeasureError::NoServing { food: food.name.clone() });
+    }
+    // The food's own measures come first: its "cup" may be packed or heaped.
+    if let Some(measure) = food.measures.iter().find(|measure| same_measure(&measure.name, unit)) {
+        return Ok(measure.grams);
+    }
+
+    match standard_unit(unit) {
+        Some(StandardUnit::Mass(grams)) => Ok(grams),
+        Some(StandardUnit::Volume(ml)) => match density(food) {
+            Some(grams_per_ml) => Ok(ml * grams_per_ml),
+            None => Err(MeasureError::NeedsDensity { food: food.name.clone(), measure: unit.to_string() }),
+        },
+        None => Err(MeasureError::UnknownMeasure { food: food.name.clone(), measure: unit.to_string() }),
+    }
+}
+
+// Grams per millilitre, from the first of the food's measures that is a volume.
+fn density(food: &Food) -> Option<f32> {
+    food.measures.iter().find_map(|measure| match standard_unit(&measure.name) {
+        Some(StandardUnit::Volume(ml)) => Some(measure.grams / ml),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bread() -> Food {
+        Food {
+            name: "bread".to_string(),
+            serving_grams: Some(60.0),
+            measures: vec![
+                Measure::new("Slice", 1.0, 30.0).unwrap(),
+                Measure::new("cup", 2.0, 90.0).unwrap(),
+            ],
+            ..Food::default()
+        }
+    }
+
+    #[test]
+    fn amounts_are_parsed() {
+        assert_eq!(parse_amount("2 slices"), Ok((2.0, "slices".to_string())));
+        assert_eq!(parse_amount("1 1/2 Cups"), Ok((1.5, "cups".to_string())));
+        assert_eq!(parse_amount("150g"), Ok((150.0, "g".to_string())));
+        assert_eq!(parse_amount("0.25 fl oz"), Ok((0.25, "fl oz".to_string())));
+        assert_eq!(parse_amount("80"), Ok((80.0, String::new())));
+        assert_eq!(parse_amount("a slice"), Err(MeasureError::NoAmount("a slice".to_string())));
+        assert_eq!(parse_amount("0 g"), Err(MeasureError::NotPositive));
+    }
+
+    #[test]
+    fn measures_convert_to_grams() {
+        let bread = bread();
+        assert_eq!(to_grams(&bread, "2 slices"), Ok(60.0));
+        assert_eq!(to_grams(&bread, "1 serving"), Ok(60.0));
+        assert_eq!(to_grams(&bread, "80"), Ok(80.0));
+        assert!((to_grams(&bread, "2 oz").unwrap() - 56.699).abs() < 0.01);
+
+        // The cup measure gives the density for other volumes: 16 tbsp a cup.
+        assert!((to_grams(&bread, "1 tbsp").unwrap() - 45.0 / 16.0).abs() < 0.01);
+
+        assert_eq!(
+            to_grams(&bread, "1 loaf"),
+            Err(MeasureError::UnknownMeasure { food: "bread".to_string(), measure: "loaf".to_string() })
+        );
+    }
+
+    #[test]
+    fn volumes_need_a_density() {
+        let mut milk = Food { name: "milk".to_string(), ..Food::default() };
+        assert_eq!(
+            to_grams(&milk, "1 cup"),
+            Err(MeasureError::NeedsDensity { food: "milk".to_string(), measure: "cup".to_string() })
+        );
+
+        milk.measures.push(Measure::new("ml", 1.0, 1.03).unwrap());
+        assert!((to_grams(&milk, "250 ml").unwrap() - 257.5).abs() < 0.01);
+        // Weights need no measure of their own, so sources' "g" ones are dropped.
+        assert_eq!(Measure::new("g", 100.0, 100.0), None);
+    }
+}
@@ -0,0 +1,202 @@
+use rusqlite::Connection;
+use thiserror::Error;
+
+// Ordered up-migrations. Entry N brings the schema from version N to N + 1,
+// so never edit or reorder an existing file, only append new ones.
+const MIGRATIONS: &[&str] = &[
+    include_str!("../migrations/0001_initial_schema.sql"),
+    include_str!("../migrations/0002_normalize_nutrients.sql"),
+    include_str!("../migrations/0003_lookup_cache.sql"),
+    include_str!("../migrations/0004_pending_lookups.sql"),
+    include_str!("../migrations/0005_food_details.sql"),
+    include_str!("../migrations/0006_per_100_grams.sql"),
+    include_str!("../migrations/0007_food_measures.sql"),
+    include_str!("../migrations/0008_food_search.sql"),
+];
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("Database schema version {found} is newer than this build supports ({supported})")]
+    TooNew { found: u32, supported: u32 },
+    #[error("Migration to schema version {version} failed: {source}")]
+    Failed { version: u32, source: rusqlite::Error },
+    #[error("Database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.len() as u32
+}
+
+pub fn schema_version(conn: &Connection) -> Result<u32, rusqlite::Error> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+pub fn run_migrations(conn: &mut Connection) -> Result<u32, MigrationError> {
+    let current = schema_version(conn)?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(MigrationError::TooNew { found: current, supported: latest });
+    }
+
+    // Rebuilding a table, as versions 2 and 6 do, drops one that others
+    // reference, which the bundled SQLite's foreign key enforcement would
+    // refuse. It can't be switched inside a transaction, so it is off for
+    // the whole run and put back afterwards.
+    let foreign_keys: bool = conn.pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
+    conn.pragma_update(None, "foreign_keys", false)?;
+    let migrated = apply_migrations(conn, current);
+    conn.pragma_update(None, "foreign_keys", foreign_keys)?;
+    migrated?;
+
+    Ok(latest)
+}
+
+fn apply_migrations(conn: &mut Connection, current: u32) -> Result<(), MigrationError> {
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let version = index as u32 + 1;
+
+        // user_version lives in the database header, so bumping it inside the
+        // transaction keeps the schema and its version number in step.
+        let apply = |conn: &mut Connection| -> Result<(), rusqlite::Error> {
+            let tx = conn.transaction()?;
+            tx.execute_batch(sql)?;
+            tx.pragma_update(None, "user_version", version)?;
+            tx.commit()
+        };
+
+        apply(conn).map_err(|source| MigrationError::Failed { version, source })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn newer_schema_is_refused() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+
+        match run_migrations(&mut conn) {
+            Err(MigrationError::TooNew { found, supported }) => {
+                assert_eq!(found, latest_version() + 1);
+                assert_eq!(supported, latest_version());
+            }
+            other => panic!("expected TooNew, got {:?}", other),
+        }
+        assert_eq!(schema_version(&conn).unwrap(), latest_version() + 1);
+    }
+
+    // Databases from before versioning have the tables create_database() made
+    // and user_version 0. Their rows have to come through every migration.
+    #[test]
+    fn unversioned_database_is_adopted() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/migrations/baseline_schema.sql");
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+
+        // Every one of the 99 nutrient columns set, protein and energy to
+        // something recognizable.
+        let columns: Vec<String> = conn
+            .prepare("SELECT name FROM pragma_table_info('food_items') WHERE name NOT IN ('id', 'name', 'weight_grams')")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(columns.len(), 99);
+        let values: Vec<&str> = columns
+            .iter()
+            .map(|column| match column.as_str() {
+                "protein" => "4.5",
+                "energy" => "130",
+                _ => "0",
+            })
+            .collect();
+        conn.execute_batch(&format!(
+            "INSERT INTO food_items (id, name, weight_grams, {}) VALUES (1, 'rice', 100, {});
+             INSERT INTO entries (id, timestamp) VALUES (1, '2024-05-01 12:00:00');
+             INSERT INTO entry_foods (entry_id, food_id, weight_grams) VALUES (1, 1, 150);",
+            columns.join(", "),
+            values.join(", ")
+        ))
+        .unwrap();
+
+        assert_eq!(run_migrations(&mut conn).unwrap(), latest_version());
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+
+        let stored: u32 = conn
+            .query_row("SELECT COUNT(*) FROM food_nutrients WHERE food_id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, 99);
+        let amount = |key: &str| -> f64 {
+            conn.query_row(
+                "SELECT fn.amount FROM food_nutrients fn JOIN nutrients n ON n.id = fn.nutrient_id
+                 WHERE fn.food_id = 1 AND n.key = ?1",
+                [key],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(amount("protein"), 4.5);
+        assert_eq!(amount("energy"), 130.0);
+
+        let eaten: f64 = conn
+            .query_row("SELECT weight_grams FROM entry_foods WHERE entry_id = 1 AND food_id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(eaten, 150.0);
+    }
+
+    // Version 6 rescales what earlier versions stored per serving.
+    #[test]
+    fn amounts_are_moved_to_100_grams() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        for sql in &MIGRATIONS[..5] {
+            conn.execute_batch(sql).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 5).unwrap();
+        conn.execute_batch(
+            "INSERT INTO food_items (id, name, weight_grams, serving_label, serving_grams) VALUES
+                 (1, 'rice', 200, '1 cup', NULL), (2, 'oats', 100, NULL, 40), (3, 'broken', 0, NULL, NULL),
+                 (4, 'egg', 100, '2 large', NULL), (5, 'apple', 100, NULL, NULL);
+             INSERT INTO food_nutrients (food_id, nutrient_id, amount)
+                 SELECT food.id, nutrients.id, food.amount
+                 FROM (SELECT 1 AS id, 4.0 AS amount UNION ALL SELECT 2, 13.0 UNION ALL SELECT 3, 1.0) food
+                 JOIN nutrients ON nutrients.key = 'protein';",
+        )
+        .unwrap();
+
+        assert_eq!(run_migrations(&mut conn).unwrap(), latest_version());
+
+        let protein = |name: &str| -> Option<f64> {
+            conn.query_row(
+                "SELECT fn.amount FROM food_nutrients fn
+                 JOIN food_items f ON f.id = fn.food_id
+                 WHERE f.name = ?1",
+                [name],
+                |row| row.get(0),
+            )
+            .ok()
+        };
+        assert_eq!(protein("rice"), Some(2.0));
+        assert_eq!(protein("oats"), Some(13.0));
+        assert_eq!(protein("broken"), None);
+
+        let serving = |name: &str| -> Option<f64> {
+            conn.query_row("SELECT serving_grams FROM food_items WHERE name = ?1", [name], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(serving("rice"), Some(200.0));
+        assert_eq!(serving("oats"), Some(40.0));
+        assert_eq!(serving("broken"), None);
+        // A labelled 100 g serving is a serving; an unlabelled 100 g was per 100 g.
+        assert_eq!(serving("egg"), Some(100.0));
+        assert_eq!(serving("apple"), None);
+    }
+}
@@ -0,0 +1,438 @@
+// The one list of nutrients the app knows about. Database seeding, the
+// Nutritionix attr_id mapping and every label in the UI are derived from it.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum Unit {
+    Gram,
+    Milligram,
+    Microgram,
+    Kilocalorie,
+    Kilojoule,
+    InternationalUnit,
+}
+
+impl Unit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Gram => "g",
+            Unit::Milligram => "mg",
+            Unit::Microgram => "µg",
+            Unit::Kilocalorie => "kcal",
+            Unit::Kilojoule => "kJ",
+            Unit::InternationalUnit => "IU",
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, PartialOrd, Ord)]
+pub enum NutrientCategory {
+    Macronutrient,
+    Vitamin,
+    Mineral,
+    AminoAcid,
+    Sterol,
+    Carotenoid,
+    Other,
+}
+
+impl NutrientCategory {
+    pub fn label(self) -> &'static str {
+        match self {
+            NutrientCategory::Macronutrient => "Macronutrients",
+            NutrientCategory::Vitamin => "Vitamins",
+            NutrientCategory::Mineral => "Minerals",
+            NutrientCategory::AminoAcid => "Amino acids",
+            NutrientCategory::Sterol => "Sterols",
+            NutrientCategory::Carotenoid => "Carotenoids",
+            NutrientCategory::Other => "Other",
+        }
+    }
+}
+
+// One variant per registry row, declared in the same order as NUTRIENTS so
+// a kind can index straight into it.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, PartialOrd, Ord)]
+pub enum NutrientKind {
+    Calcium,
+    Carbohydrate,
+    Cholesterol,
+    Energy,
+    SaturatedFat,
+    TotalFat,
+    TransFat,
+    Iron,
+    Fiber,
+    Potassium,
+    Sodium,
+    Protein,
+    TotalSugars,
+    AddedSugars,
+    VitaminD,
+    Alanine,
+    Alcohol,
+    Arginine,
+    Ash,
+    AsparticAcid,
+    Betaine,
+    Caffeine,
+    Campesterol,
+    AlphaCarotene,
+    BetaCarotene,
+    VitaminD3,
+    Choline,
+    BetaCryptoxanthin,
+    Copper,
+    Cystine,
+    EnergyKj,
+    VitaminD2,
+    MonounsaturatedFat,
+    PolyunsaturatedFat,
+    TransMonoenoicFat,
+    TransPolyenoicFat,
+    Fluoride,
+    FolateTotal,
+    FolicAcid,
+    FolateDfe,
+    FolateFood,
+    Fructose,
+    Galactose,
+    GlutamicAcid,
+    Glucose,
+    Glycine,
+    Histidine,
+    Hydroxyproline,
+    Isoleucine,
+    Lactose,
+    Leucine,
+    LuteinZeaxanthin,
+    Lycopene,
+    Lysine,
+    Maltose,
+    Methionine,
+    Magnesium,
+    Menaquinone,
+    Manganese,
+    Niacin,
+    VitaminEAdded,
+    VitaminB12Added,
+    AdjustedProtein,
+    Phosphorus,
+    PantothenicAcid,
+    Phenylalanine,
+    Phytosterols,
+    Proline,
+    Retinol,
+    Riboflavin,
+    Selenium,
+    Serine,
+    BetaSitosterol,
+    Starch,
+    Stigmasterol,
+    Sucrose,
+    Theobromine,
+    Thiamin,
+    Threonine,
+    VitaminE,
+    BetaTocopherol,
+    DeltaTocopherol,
+    GammaTocopherol,
+    Tryptophan,
+    Tyrosine,
+    Valine,
+    VitaminAIu,
+    VitaminARae,
+    VitaminB12,
+    VitaminB6,
+    VitaminC,
+    VitaminK,
+    Dihydrophylloquinone,
+    Water,
+    Zinc,
+    AlphaTocotrienol,
+    BetaTocotrienol,
+    GammaTocotrienol,
+    DeltaTocotrienol,
+}
+
+impl NutrientKind {
+    pub fn all() -> impl Iterator<Item = NutrientKind> {
+        NUTRIENTS.iter().map(|nutrient| nutrient.kind)
+    }
+
+    pub fn from_key(key: &str) -> Option<NutrientKind> {
+        by_key(key).map(|nutrient| nutrient.kind)
+    }
+
+    pub fn from_attr_id(attr_id: u32) -> Option<NutrientKind> {
+        by_attr_id(attr_id).map(|nutrient| nutrient.kind)
+    }
+
+    pub fn info(self) -> &'static NutrientInfo {
+        &NUTRIENTS[self as usize]
+    }
+
+    pub fn key(self) -> &'static str {
+        self.info().key
+    }
+
+    pub fn attr_id(self) -> u32 {
+        self.info().attr_id
+    }
+
+    pub fn name(self) -> &'static str {
+        self.info().name
+    }
+
+    pub fn unit(self) -> Unit {
+        self.info().unit
+    }
+
+    pub fn category(self) -> NutrientCategory {
+        self.info().category
+    }
+
+    pub fn daily_value(self) -> Option<f32> {
+        self.info().daily_value
+    }
+}
+
+impl fmt::Display for NutrientKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// Serialized as the registry key, which stays stable if variants are renamed.
+impl Serialize for NutrientKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.key())
+    }
+}
+
+impl<'de> Deserialize<'de> for NutrientKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<NutrientKind, D::Error> {
+        let key = String::deserialize(deserializer)?;
+        NutrientKind::from_key(&key).ok_or_else(|| de::Error::custom(format!("unknown nutrient \"{}\"", key)))
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct NutrientInfo {
+    pub kind: NutrientKind,
+    // Stable identifier stored in the nutrients table.
+    pub key: &'static str,
+    // USDA nutrient number, which Nutritionix reports as attr_id.
+    pub attr_id: u32,
+    pub name: &'static str,
+    pub unit: Unit,
+    pub category: NutrientCategory,
+    // FDA daily value for adults in `unit`, where one is defined.
+    pub daily_value: Option<f32>,
+}
+
+const fn info(
+    kind: NutrientKind,
+    attr_id: u32,
+    key: &'static str,
+    name: &'static str,
+    unit: Unit,
+    category: NutrientCategory,
+    daily_value: Option<f32>,
+) -> NutrientInfo {
+    NutrientInfo { kind, key, attr_id, name, unit, category, daily_value }
+}
+
+use NutrientCategory::*;
+use NutrientKind::*;
+use Unit::*;
+
+pub static NUTRIENTS: &[NutrientInfo] = &[
+    info(Calcium, 301, "calcium_ca", "Calcium, Ca", Milligram, Mineral, Some(1300.0)),
+    info(Carbohydrate, 205, "carbohydrates", "Carbohydrate, by difference", Gram, Macronutrient, Some(275.0)),
+    info(Cholesterol, 601, "cholesterol", "Cholesterol", Milligram, Sterol, Some(300.0)),
+    info(Energy, 208, "energy", "Energy", Kilocalorie, Macronutrient, Some(2000.0)),
+    info(SaturatedFat, 606, "fatty_acids_saturated", "Fatty acids, total saturated", Gram, Macronutrient, Some(20.0)),
+    info(TotalFat, 204, "total_lipid_fat", "Total lipid (fat)", Gram, Macronutrient, Some(78.0)),
+    info(TransFat, 605, "fatty_acids_trans", "Fatty acids, total trans", Gram, Macronutrient, None),
+    info(Iron, 303, "iron_fe", "Iron, Fe", Milligram, Mineral, Some(18.0)),
+    info(Fiber, 291, "fiber_dietary", "Fiber, total dietary", Gram, Macronutrient, Some(28.0)),
+    info(Potassium, 306, "potassium_k", "Potassium, K", Milligram, Mineral, Some(4700.0)),
+    info(Sodium, 307, "sodium_na", "Sodium, Na", Milligram, Mineral, Some(2300.0)),
+    info(Protein, 203, "protein", "Protein", Gram, Macronutrient, Some(50.0)),
+    info(TotalSugars, 269, "sugars_total", "Sugars, total", Gram, Macronutrient, None),
+    info(AddedSugars, 539, "sugars_added", "Sugars, added", Gram, Macronutrient, Some(50.0)),
+    info(VitaminD, 324, "vitamin_d", "Vitamin D", InternationalUnit, Vitamin, Some(800.0)),
+    info(Alanine, 513, "alanine", "Alanine", Gram, AminoAcid, None),
+    info(Alcohol, 221, "alcohol_ethyl", "Alcohol, ethyl", Gram, Macronutrient, None),
+    info(Arginine, 511, "arginine", "Arginine", Gram, AminoAcid, None),
+    info(Ash, 207, "ash", "Ash", Gram, Other, None),
+    info(AsparticAcid, 514, "aspartic_acid", "Aspartic acid", Gram, AminoAcid, None),
+    info(Betaine, 454, "betaine", "Betaine", Milligram, Other, None),
+    info(Caffeine, 262, "caffeine", "Caffeine", Milligram, Other, None),
+    info(Campesterol, 639, "campesterol", "Campesterol", Milligram, Sterol, None),
+    info(AlphaCarotene, 322, "carotene_alpha", "Carotene, alpha", Microgram, Carotenoid, None),
+    info(BetaCarotene, 321, "carotene_beta", "Carotene, beta", Microgram, Carotenoid, None),
+    info(VitaminD3, 326, "vitamin_d3", "Vitamin D3 (cholecalciferol)", Microgram, Vitamin, None),
+    info(Choline, 421, "choline_total", "Choline, total", Milligram, Vitamin, Some(550.0)),
+    info(BetaCryptoxanthin, 334, "cryptoxanthin_beta", "Cryptoxanthin, beta", Microgram, Carotenoid, None),
+    info(Copper, 312, "copper_cu", "Copper, Cu", Milligram, Mineral, Some(0.9)),
+    info(Cystine, 507, "cystine", "Cystine", Gram, AminoAcid, None),
+    info(EnergyKj, 268, "energy_kj", "Energy (kJ)", Kilojoule, Macronutrient, None),
+    info(VitaminD2, 325, "vitamin_d2", "Vitamin D2 (ergocalciferol)", Microgram, Vitamin, None),
+    info(MonounsaturatedFat, 645, "fatty_acids_monounsaturated", "Fatty acids, total monounsaturated", Gram, Macronutrient, None),
+    info(PolyunsaturatedFat, 646, "fatty_acids_polyunsaturated", "Fatty acids, total polyunsaturated", Gram, Macronutrient, None),
+    info(TransMonoenoicFat, 693, "fatty_acids_transmonoenoic", "Fatty acids, total trans-monoenoic", Gram, Macronutrient, None),
+    info(TransPolyenoicFat, 695, "fatty_acids_transpolyenoic", "Fatty acids, total trans-polyenoic", Gram, Macronutrient, None),
+    info(Fluoride, 313, "fluoride_f", "Fluoride, F", Microgram, Mineral, None),
+    info(FolateTotal, 417, "folate_total", "Folate, total", Microgram, Vitamin, None),
+    info(FolicAcid, 431, "folic_acid", "Folic acid", Microgram, Vitamin, None),
+    info(FolateDfe, 435, "folate_dfe", "Folate, DFE", Microgram, Vitamin, Some(400.0)),
+    info(FolateFood, 432, "folate_food", "Folate, food", Microgram, Vitamin, None),
+    info(Fructose, 212, "fructose", "Fructose", Gram, Macronutrient, None),
+    info(Galactose, 287, "galactose", "Galactose", Gram, Macronutrient, None),
+    info(GlutamicAcid, 515, "glutamic_acid", "Glutamic acid", Gram, AminoAcid, None),
+    info(Glucose, 211, "glucose_dextrose", "Glucose (dextrose)", Gram, Macronutrient, None),
+    info(Glycine, 516, "glycine", "Glycine", Gram, AminoAcid, None),
+    info(Histidine, 512, "histidine", "Histidine", Gram, AminoAcid, None),
+    info(Hydroxyproline, 521, "hydroxyproline", "Hydroxyproline", Gram, AminoAcid, None),
+    info(Isoleucine, 503, "isoleucine", "Isoleucine", Gram, AminoAcid, None),
+    info(Lactose, 213, "lactose", "Lactose", Gram, Macronutrient, None),
+    info(Leucine, 504, "leucine", "Leucine", Gram, AminoAcid, None),
+    info(LuteinZeaxanthin, 338, "lutein_zeaxanthin", "Lutein + zeaxanthin", Microgram, Carotenoid, None),
+    info(Lycopene, 337, "lycopene", "Lycopene", Microgram, Carotenoid, None),
+    info(Lysine, 505, "lysine", "Lysine", Gram, AminoAcid, None),
+    info(Maltose, 214, "maltose", "Maltose", Gram, Macronutrient, None),
+    info(Methionine, 506, "methionine", "Methionine", Gram, AminoAcid, None),
+    info(Magnesium, 304, "magnesium_mg", "Magnesium, Mg", Milligram, Mineral, Some(420.0)),
+    info(Menaquinone, 428, "menaquinone", "Menaquinone-4", Microgram, Vitamin, None),
+    info(Manganese, 315, "manganese_mn", "Manganese, Mn", Milligram, Mineral, Some(2.3)),
+    info(Niacin, 406, "niacin", "Niacin", Milligram, Vitamin, Some(16.0)),
+    info(VitaminEAdded, 573, "vitamin_e_added", "Vitamin E, added", Milligram, Vitamin, None),
+    info(VitaminB12Added, 578, "vitamin_b_added", "Vitamin B-12, added", Microgram, Vitamin, None),
+    info(AdjustedProtein, 257, "adjusted_protein", "Adjusted protein", Gram, Macronutrient, None),
+    info(Phosphorus, 305, "phosphorus_p", "Phosphorus, P", Milligram, Mineral, Some(1250.0)),
+    info(PantothenicAcid, 410, "pantothenic_acid", "Pantothenic acid", Milligram, Vitamin, Some(5.0)),
+    info(Phenylalanine, 508, "phenylalanine", "Phenylalanine", Gram, AminoAcid, None),
+    info(Phytosterols, 636, "phytosterols", "Phytosterols", Milligram, Sterol, None),
+    info(Proline, 517, "proline", "Proline", Gram, AminoAcid, None),
+    info(Retinol, 319, "retinol", "Retinol", Microgram, Vitamin, None),
+    info(Riboflavin, 405, "riboflavin", "Riboflavin", Milligram, Vitamin, Some(1.3)),
+    info(Selenium, 317, "selenium_se", "Selenium, Se", Microgram, Mineral, Some(55.0)),
+    info(Serine, 518, "serine", "Serine", Gram, AminoAcid, None),
+    info(BetaSitosterol, 641, "betasitosterol", "Beta-sitosterol", Milligram, Sterol, None),
+    info(Starch, 209, "starch", "Starch", Gram, Macronutrient, None),
+    info(Stigmasterol, 638, "stigmasterol", "Stigmasterol", Milligram, Sterol, None),
+    info(Sucrose, 210, "sucrose", "Sucrose", Gram, Macronutrient, None),
+    info(Theobromine, 263, "theobromine", "Theobromine", Milligram, Other, None),
+    info(Thiamin, 404, "thiamin", "Thiamin", Milligram, Vitamin, Some(1.2)),
+    info(Threonine, 502, "threonine", "Threonine", Gram, AminoAcid, None),
+    info(VitaminE, 323, "vitamin_e_alphatocopherol", "Vitamin E (alpha-tocopherol)", Milligram, Vitamin, Some(15.0)),
+    info(BetaTocopherol, 341, "tocopherol_beta", "Tocopherol, beta", Milligram, Vitamin, None),
+    info(DeltaTocopherol, 343, "tocopherol_delta", "Tocopherol, delta", Milligram, Vitamin, None),
+    info(GammaTocopherol, 342, "tocopherol_gamma", "Tocopherol, gamma", Milligram, Vitamin, None),
+    info(Tryptophan, 501, "tryptophan", "Tryptophan", Gram, AminoAcid, None),
+    info(Tyrosine, 509, "tyrosine", "Tyrosine", Gram, AminoAcid, None),
+    info(Valine, 510, "valine", "Valine", Gram, AminoAcid, None),
+    info(VitaminAIu, 318, "vitamin_a_iu", "Vitamin A, IU", InternationalUnit, Vitamin, None),
+    info(VitaminARae, 320, "vitamin_a_rae", "Vitamin A, RAE", Microgram, Vitamin, Some(900.0)),
+    info(VitaminB12, 418, "vitamin_b12", "Vitamin B-12", Microgram, Vitamin, Some(2.4)),
+    info(VitaminB6, 415, "vitamin_b6", "Vitamin B-6", Milligram, Vitamin, Some(1.7)),
+    info(VitaminC, 401, "vitamin_c_total_ascorbic_acid", "Vitamin C, total ascorbic acid", Milligram, Vitamin, Some(90.0)),
+    info(VitaminK, 430, "vitamin_k_phylloquinone", "Vitamin K (phylloquinone)", Microgram, Vitamin, Some(120.0)),
+    info(Dihydrophylloquinone, 429, "dihydrophylloquinone", "Dihydrophylloquinone", Microgram, Vitamin, None),
+    info(Water, 255, "water", "Water", Gram, Macronutrient, None),
+    info(Zinc, 309, "zinc_zn", "Zinc, Zn", Milligram, Mineral, Some(11.0)),
+    info(AlphaTocotrienol, 344, "tocotrienol_alpha", "Tocotrienol, alpha", Milligram, Vitamin, None),
+    info(BetaTocotrienol, 345, "tocotrienol_beta", "Tocotrienol, beta", Milligram, Vitamin, None),
+    info(GammaTocotrienol, 346, "tocotrienol_gamma", "Tocotrienol, gamma", Milligram, Vitamin, None),
+    info(DeltaTocotrienol, 347, "tocotrienol_delta", "Tocotrienol, delta", Milligram, Vitamin, None),
+];
+
+pub fn by_key(key: &str) -> Option<&'static NutrientInfo> {
+    NUTRIENTS.iter().find(|nutrient| nutrient.key == key)
+}
+
+pub fn by_attr_id(attr_id: u32) -> Option<&'static NutrientInfo> {
+    NUTRIENTS.iter().find(|nutrient| nutrient.attr_id == attr_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_entry::Database;
+    use crate::nutrition_api::get_nutrient_name_map;
+    use std::collections::HashSet;
+
+    #[test]
+    fn keys_and_attr_ids_are_unique() {
+        let keys: HashSet<_> = NUTRIENTS.iter().map(|nutrient| nutrient.key).collect();
+        let attr_ids: HashSet<_> = NUTRIENTS.iter().map(|nutrient| nutrient.attr_id).collect();
+
+        assert_eq!(keys.len(), NUTRIENTS.len());
+        assert_eq!(attr_ids.len(), NUTRIENTS.len());
+    }
+
+    #[test]
+    fn kinds_index_their_own_row() {
+        for (index, nutrient) in NUTRIENTS.iter().enumerate() {
+            assert_eq!(nutrient.kind as usize, index, "{:?}", nutrient.kind);
+            assert_eq!(NutrientKind::from_key(nutrient.key), Some(nutrient.kind));
+            assert_eq!(NutrientKind::from_attr_id(nutrient.attr_id), Some(nutrient.kind));
+        }
+    }
+
+    #[test]
+    fn api_mapping_matches_registry() {
+        let map = get_nutrient_name_map();
+
+        assert_eq!(map.len(), NUTRIENTS.len());
+        for nutrient in NUTRIENTS {
+            assert_eq!(map.get(&nutrient.attr_id), Some(&nutrient.key));
+        }
+    }
+
+    #[test]
+    fn migration_seed_agrees_with_registry() {
+        // Version 2 converted the old columns by key using its own seed list,
+        // so any key it knows must still mean the same attr_id.
+        let seed = include_str!("../migrations/0002_normalize_nutrients.sql");
+        let mut seen = 0;
+
+        for line in seed.lines().filter(|line| line.starts_with("    ('")) {
+            let key = &line[6..line.find("', '").unwrap()];
+            let attr_id: u32 = line[line.rfind(", ").unwrap() + 2..line.rfind(')').unwrap()].parse().unwrap();
+
+            assert_eq!(by_key(key).map(|nutrient| nutrient.attr_id), Some(attr_id), "{}", key);
+            seen += 1;
+        }
+
+        assert_eq!(seen, NUTRIENTS.len());
+    }
+
+    #[test]
+    fn database_dictionary_matches_registry() {
+        let db = Database::open(":memory:").unwrap();
+
+        for nutrient in NUTRIENTS {
+            let (attr_id, name, unit): (u32, String, String) = db
+                .conn
+                .query_row(
+                    "SELECT attr_id, name, unit FROM nutrients WHERE key = ?1",
+                    [nutrient.key],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .unwrap();
+
+            assert_eq!((attr_id, name.as_str(), unit.as_str()), (nutrient.attr_id, nutrient.name, nutrient.unit.symbol()));
+        }
+    }
+}
@@ -1,9 +1,18 @@
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder, Response};
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::error::Error;
 use std::io::{self, Write};
-use thiserror::Error;
+use async_trait::async_trait;
+
+use crate::config::Config;
+use crate::credentials::Credentials;
+use crate::http::{send_with_retry, shared_client, DailyQuota, QuotaUsage, RetryPolicy};
+use crate::measures::Measure;
+use crate::nutrients::NUTRIENTS;
+use crate::provider::{AmountBasis, NutritionProvider, ProviderFood, QueryError, SearchHit};
+
+const DEFAULT_BASE_URL: &str = "https://trackapi.nutritionix.com";
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Nutrient {
@@ -16,6 +25,25 @@ pub struct ApiFood {
     pub food_name: String,
     pub serving_weight_grams: f32,
     pub full_nutrients: Vec<Nutrient>,
+    #[serde(default)]
+    pub brand_name: Option<String>,
+    #[serde(default)]
+    pub nix_item_id: Option<String>,
+    #[serde(default)]
+    pub serving_qty: Option<f32>,
+    #[serde(default)]
+    pub serving_unit: Option<String>,
+    // Null for most branded foods.
+    #[serde(default)]
+    pub alt_measures: Option<Vec<AltMeasure>>,
+}
+
+// `qty` of `measure` weighs `serving_weight` grams.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AltMeasure {
+    pub serving_weight: f32,
+    pub measure: String,
+    pub qty: f32,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -23,155 +51,234 @@ pub struct NutritionxResponse {
     pub foods: Vec<ApiFood>,
 }
 
-#[derive(Debug, Error)]
-pub enum QueryError {
-    #[error("Network request failed: {0}")]
-    Network(#[from] reqwest::Error),
-    #[error("No results found for the given query")]
-    NotFound,
+#[derive(Deserialize, Debug, Clone)]
+struct CommonHit {
+    food_name: String,
+    #[serde(default)]
+    serving_qty: Option<f32>,
+    #[serde(default)]
+    serving_unit: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct BrandedHit {
+    food_name: String,
+    #[serde(default)]
+    brand_name: Option<String>,
+    nix_item_id: String,
+    #[serde(default)]
+    serving_qty: Option<f32>,
+    #[serde(default)]
+    serving_unit: Option<String>,
+    #[serde(default)]
+    nf_calories: Option<f32>,
+}
+
+fn describe_serving(qty: Option<f32>, unit: Option<String>) -> Option<String> {
+    match (qty, unit) {
+        (Some(qty), Some(unit)) => Some(format!("{} {}", qty, unit)),
+        (None, Some(unit)) => Some(unit),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct InstantResponse {
+    #[serde(default)]
+    common: Vec<CommonHit>,
+    #[serde(default)]
+    branded: Vec<BrandedHit>,
+}
+
+impl From<ApiFood> for ProviderFood {
+    fn from(food: ApiFood) -> ProviderFood {
+        let mut converted = ProviderFood {
+            name: food.food_name,
+            brand: food.brand_name,
+            serving_weight_grams: food.serving_weight_grams,
+            basis: AmountBasis::PerServing,
+            serving_label: describe_serving(food.serving_qty, food.serving_unit.clone()),
+            ..ProviderFood::default()
+        };
+
+        // The serving itself, e.g. 2 "large" weighing 100 g, is a measure too.
+        let serving = food
+            .serving_unit
+            .map(|unit| (unit, food.serving_qty.unwrap_or(1.0), food.serving_weight_grams));
+        let alternatives = food
+            .alt_measures
+            .unwrap_or_default()
+            .into_iter()
+            .map(|alt| (alt.measure, alt.qty, alt.serving_weight));
+        for (name, qty, grams) in serving.into_iter().chain(alternatives) {
+            if let Some(measure) = Measure::new(&name, qty, grams) {
+                if !converted.measures.iter().any(|known| known.name == measure.name) {
+                    converted.measures.push(measure);
+                }
+            }
+        }
+
+        for nutrient in food.full_nutrients {
+            converted.insert_usda(nutrient.attr_id, nutrient.value);
+        }
+
+        converted
+    }
 }
 
 pub fn get_nutrient_name_map() -> HashMap<u32, &'static str> {
-    let nutrient_map: HashMap<u32, &str> = [
-        (301, "calcium_ca"),
-        (205, "carbohydrates"),
-        (601, "cholesterol"),
-        (208, "energy"),
-        (606, "fatty_acids_saturated"),
-        (204, "total_lipid_fat"),
-        (605, "fatty_acids_trans"),
-        (303, "iron_fe"),    
-        (291, "fiber_dietary"),
-        (306, "potassium_k"),
-        (307, "sodium_na"),
-        (203, "protein"),
-        (269, "sugars_total"),
-        (539, "sugars_added"),
-        (324, "vitamin_d"),
-        (513, "alanine"),
-        (221, "alcohol_ethyl"),
-        (511, "arginine"),
-        (207, "ash"),
-        (514, "aspartic_acid"),
-        (454, "betaine"),
-        (262, "caffeine"),
-        (639, "campesterol"),
-        (322, "carotene_alpha"),
-        (321, "carotene_beta"),
-        (326, "vitamin_d3"),
-        (421, "choline_total"),
-        (334, "cryptoxanthin_beta"),
-        (312, "copper_cu"),
-        (507, "cystine"),
-        (268, "energy_kj"),
-        (325, "vitamin_d2"),
-        (645, "fatty_acids_monounsaturated"),
-        (646, "fatty_acids_polyunsaturated"),
-        (693, "fatty_acids_transmonoenoic"),
-        (695, "fatty_acids_transpolyenoic"),
-        (313, "fluoride_f"),
-        (417, "folate_total"),
-        (431, "folic_acid"),
-        (435, "folate_dfe"),
-        (432, "folate_food"),
-        (212, "fructose"),
-        (287, "galactose"),
-        (515, "glutamic_acid"),
-        (211, "glucose_dextrose"),
-        (516, "glycine"),
-        (512, "histidine"),
-        (521, "hydroxyproline"),
-        (503, "isoleucine"),
-        (213, "lactose"),
-        (504, "leucine"),
-        (338, "lutein_zeaxanthin"),
-        (337, "lycopene"),
-        (505, "lysine"),
-        (214, "maltose"),
-        (506, "methionine"),
-        (304, "magnesium_mg"),
-        (428, "menaquinone"),
-        (315, "manganese_mn"),
-        (406, "niacin"),
-        (573, "vitamin_e_added"),
-        (578, "vitamin_b_added"),
-        (257, "adjusted_protein"),
-        (305, "phosphorus_p"),
-        (410, "pantothenic_acid"),
-        (508, "phenylalanine"),
-        (636, "phytosterols"),
-        (517, "proline"),
-        (319, "retinol"),
-        (405, "riboflavin"),
-        (317, "selenium_se"),
-        (518, "serine"),
-        (641, "betasitosterol"),
-        (209, "starch"),
-        (638, "stigmasterol"),
-        (210, "sucrose"),
-        (263, "theobromine"),
-        (404, "thiamin"),
-        (502, "threonine"),
-        (323, "vitamin_e_alphatocopherol"),
-        (341, "tocopherol_beta"),
-        (343, "tocopherol_delta"),
-        (342, "tocopherol_gamma"),
-        (501, "tryptophan"),
-        (509, "tyrosine"),
-        (510, "valine"),
-        (318, "vitamin_a_iu"),
-        (320, "vitamin_a_rae"),
-        (418, "vitamin_b12"),
-        (415, "vitamin_b6"),
-        (401, "vitamin_c_total_ascorbic_acid"),
-        (430, "vitamin_k_phylloquinone"),
-        (429, "dihydrophylloquinone"),
-        (255, "water"),
-        (309, "zinc_zn"),
-        (344, "tocotrienol_alpha"),
-        (345, "tocotrienol_beta"),
-        (346, "tocotrienol_gamma"),
-        (347, "tocotrienol_delta")
-    ]
-    .iter()
-    .cloned()
-    .collect();
-
-    nutrient_map
+    NUTRIENTS.iter().map(|nutrient| (nutrient.attr_id, nutrient.key)).collect()
+}
+
+// Search hit ids are "common:<food name>" or "branded:<nix_item_id>", since
+// the two kinds are fetched from different endpoints.
+const COMMON_PREFIX: &str = "common:";
+const BRANDED_PREFIX: &str = "branded:";
+
+// Requests per day on Nutritionix's free plan.
+const DEFAULT_DAILY_QUOTA: u32 = 200;
+
+pub struct Nutritionix {
+    client: Client,
+    base_url: String,
+    credentials: Option<Credentials>,
+    retry_policy: RetryPolicy,
+    quota: DailyQuota,
 }
 
-pub async fn query_nutritionx(query: String) -> Result<Vec<ApiFood>, QueryError> {
-    let app_id = "68bfdaab";
-    let app_key = "ad27f9e6dc09294cdebb0cc44b30433b";
-    let client = Client::new();
-    let url = "https://trackapi.nutritionix.com/v2/natural/nutrients";
-    let nutrient_name_map = get_nutrient_name_map();
-    let mut foods = Vec::new();
-
-    let response = client
-        .post(url)
-        .header("x-app-id", app_id)
-        .header("x-app-key", app_key)
-        .json(&serde_json::json!({ "query": query }))
-        .send()
-        .await?;
-
-    if response.status().is_success() {
+impl Nutritionix {
+    pub fn new(credentials: Option<Credentials>) -> Nutritionix {
+        let config = Config::load();
+
+        Nutritionix {
+            client: shared_client(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            credentials,
+            retry_policy: RetryPolicy::from_config(&config),
+            quota: DailyQuota::load("nutritionix", config.nutritionix_daily_quota.unwrap_or(DEFAULT_DAILY_QUOTA)),
+        }
+        .with_base_url(config.nutritionix_base_url.as_deref().unwrap_or(DEFAULT_BASE_URL))
+    }
+
+    // Points at another server speaking the same API, such as one set in the
+    // config file or the stub the tests run against.
+    pub fn with_base_url(mut self, base_url: &str) -> Nutritionix {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_quota(mut self, quota: DailyQuota) -> Nutritionix {
+        self.quota = quota;
+        self
+    }
+
+    // Uses whatever credentials are configured, if any.
+    pub fn from_settings() -> Nutritionix {
+        Nutritionix::new(Credentials::resolve().map(|(credentials, _)| credentials))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response, QueryError> {
+        let credentials = self.credentials.as_ref().ok_or_else(|| {
+            QueryError::Unauthorized("No Nutritionix credentials are configured".to_string())
+        })?;
+
+        let request = request
+            .header("x-app-id", &credentials.app_id)
+            .header("x-app-key", &credentials.app_key);
+
+        send_with_retry(request, &self.retry_policy, &self.quota).await
+    }
+
+    async fn fetch_foods(&self, request: RequestBuilder) -> Result<Vec<ApiFood>, QueryError> {
+        let response = self.send(request).await?;
         let nutritionix_response: NutritionxResponse = response.json().await?;
-        foods = nutritionix_response.foods;
-    } else {
-        return Err(QueryError::NotFound);
+        Ok(nutritionix_response.foods)
     }
 
-    Ok(foods)
+    async fn fetch_item(&self, query: &[(&str, &str)]) -> Result<ProviderFood, QueryError> {
+        let request = self.client.get(self.url("/v2/search/item")).query(query);
+
+        self.fetch_foods(request)
+            .await?
+            .into_iter()
+            .next()
+            .map(ProviderFood::from)
+            .ok_or(QueryError::NotFound)
+    }
 }
 
-pub async fn query_nutritionx_cli() -> Result<Vec<NutritionxResponse>, Box<dyn Error>> {
-    let app_id = "68bfdaab";
-    let app_key = "ad27f9e6dc09294cdebb0cc44b30433b";
-    let client = Client::new();
-    let url = "https://trackapi.nutritionix.com/v2/natural/nutrients";
-    let nutrient_name_map = get_nutrient_name_map();
+#[async_trait(?Send)]
+impl NutritionProvider for Nutritionix {
+    fn name(&self) -> &'static str {
+        "Nutritionix"
+    }
+
+    fn quota(&self) -> Option<QuotaUsage> {
+        Some(self.quota.usage())
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<SearchHit>, QueryError> {
+        let request = self
+            .client
+            .get(self.url("/v2/search/instant"))
+            .query(&[("query", query)]);
+        let response = self.send(request).await?;
+
+        let instant: InstantResponse = response.json().await?;
+        let common = instant.common.into_iter().map(|hit| SearchHit {
+            id: format!("{}{}", COMMON_PREFIX, hit.food_name),
+            name: hit.food_name,
+            brand: None,
+            branded: false,
+            serving: describe_serving(hit.serving_qty, hit.serving_unit),
+            calories: None,
+        });
+        let branded = instant.branded.into_iter().map(|hit| SearchHit {
+            id: format!("{}{}", BRANDED_PREFIX, hit.nix_item_id),
+            name: hit.food_name,
+            brand: hit.brand_name,
+            branded: true,
+            serving: describe_serving(hit.serving_qty, hit.serving_unit),
+            calories: hit.nf_calories,
+        });
+
+        Ok(common.chain(branded).collect())
+    }
+
+    async fn lookup(&self, id: &str) -> Result<ProviderFood, QueryError> {
+        if let Some(food_name) = id.strip_prefix(COMMON_PREFIX) {
+            return self.parse(food_name).await?.into_iter().next().ok_or(QueryError::NotFound);
+        }
+
+        match id.strip_prefix(BRANDED_PREFIX) {
+            Some(nix_item_id) => self.fetch_item(&[("nix_item_id", nix_item_id)]).await,
+            None => Err(QueryError::NotFound),
+        }
+    }
+
+    async fn parse(&self, text: &str) -> Result<Vec<ProviderFood>, QueryError> {
+        let request = self
+            .client
+            .post(self.url("/v2/natural/nutrients"))
+            .json(&serde_json::json!({ "query": text }));
+
+        Ok(self.fetch_foods(request).await?.into_iter().map(ProviderFood::from).collect())
+    }
+
+    async fn lookup_barcode(&self, barcode: &str) -> Result<ProviderFood, QueryError> {
+        let mut food = self.fetch_item(&[("upc", barcode)]).await?;
+        food.barcode = Some(barcode.to_string());
+        Ok(food)
+    }
+}
+
+pub async fn query_nutritionx_cli() -> Result<Vec<ProviderFood>, Box<dyn Error>> {
+    let nutritionix = Nutritionix::from_settings();
     let mut collected_data = Vec::new();
 
     loop {
@@ -187,38 +294,199 @@ pub async fn query_nutritionx_cli() -> Result<Vec<NutritionxResponse>, Box<dyn E
 
         println!("{}", query);
 
-        let response = client
-            .post(url)
-            .header("x-app-id", app_id)
-            .header("x-app-key", app_key)
-            .json(&serde_json::json!({ "query": query }))
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let nutritionix_response: NutritionxResponse = response.json().await?;
-            collected_data.push(nutritionix_response.clone());
-
-            for food in nutritionix_response.foods {
-                println!("Food Name: {}", food.food_name);
-                println!("Serving Weight: {} grams", food.serving_weight_grams);
-                println!("Full Nutrients:");
-                for nutrient in &food.full_nutrients {
-                    if let Some(nutrient_name) = nutrient_name_map.get(&nutrient.attr_id) {
-                        println!(
-                            "  Name: {}, Value: {} {}",
-                            nutrient_name,
-                            nutrient.value,
-                            if *nutrient_name == "Energy" { "kcal" } else { "g" }
-                        );
+        match nutritionix.parse(&query).await {
+            Ok(foods) => {
+                for food in &foods {
+                    println!("Food Name: {}", food.name);
+                    println!("Serving Weight: {} grams", food.serving_weight_grams);
+                    println!("Full Nutrients:");
+                    for (kind, value) in &food.nutrients {
+                        println!("  Name: {}, Value: {} {}", kind, value, kind.unit());
                     }
+                    println!();
                 }
-                println!();
+                collected_data.extend(foods);
             }
-        } else {
-            println!("Food not found in Nutritionix database.");
+            Err(QueryError::NotFound) => println!("Food not found in Nutritionix database."),
+            Err(err) => return Err(Box::new(err)),
         }
     }
 
     Ok(collected_data)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::measures::to_grams;
+    use crate::nutrients::NutrientKind;
+    use crate::provider::normalize_barcode;
+    use crate::repository::{ConflictStrategy, FoodRepository, SqliteRepository};
+    use crate::stub_server::{StubRoute, StubServer, APP_ID, APP_KEY};
+
+    fn stub_credentials() -> Option<Credentials> {
+        Some(Credentials {
+            app_id: APP_ID.to_string(),
+            app_key: APP_KEY.to_string(),
+        })
+    }
+
+    fn provider_for(server: &StubServer) -> Nutritionix {
+        Nutritionix::new(stub_credentials())
+            .with_base_url(&server.url())
+            .with_quota(DailyQuota::in_memory(100))
+    }
+
+    #[tokio::test]
+    async fn parse_replays_natural_nutrients() {
+        let server = StubServer::nutritionix();
+        let foods = provider_for(&server).parse("1 cup rice and 2 eggs").await.unwrap();
+
+        assert_eq!(foods.len(), 2);
+        assert_eq!(foods[0].name, "rice");
+        assert_eq!(foods[0].serving_weight_grams, 158.0);
+        assert_eq!(foods[0].nutrients.get(&NutrientKind::Energy), Some(&205.4));
+        assert_eq!(foods[0].other_nutrients.get(&9999), Some(&1.5));
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert!(requests[0].body.contains("1 cup rice and 2 eggs"));
+    }
+
+    #[tokio::test]
+    async fn search_and_lookup_use_the_instant_and_item_endpoints() {
+        let server = StubServer::nutritionix();
+        let provider = provider_for(&server);
+
+        let hits = provider.search("rice").await.unwrap();
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0].id, "common:rice");
+        assert_eq!(hits[0].serving.as_deref(), Some("1 cup"));
+        assert!(!hits[0].branded);
+        assert!(hits[2].branded);
+        assert_eq!(hits[2].brand.as_deref(), Some("Mahatma"));
+        assert_eq!(hits[2].calories, Some(160.0));
+
+        let branded = provider.lookup(&hits[2].id).await.unwrap();
+        assert_eq!(branded.name, "Jasmine Rice");
+        assert_eq!(branded.brand.as_deref(), Some("Mahatma"));
+
+        let barcode = provider.lookup_barcode("0041196010022").await.unwrap();
+        assert_eq!(barcode.barcode.as_deref(), Some("0041196010022"));
+
+        assert!(matches!(provider.lookup_barcode("0000000000000").await, Err(QueryError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn rejected_credentials_are_reported_with_the_server_message() {
+        let server = StubServer::nutritionix();
+        let provider = Nutritionix::new(Some(Credentials {
+            app_id: "wrong".to_string(),
+            app_key: "wrong".to_string(),
+        }))
+        .with_base_url(&server.url())
+        .with_quota(DailyQuota::in_memory(100));
+
+        match provider.parse("rice").await {
+            Err(QueryError::Unauthorized(message)) => assert_eq!(message, "unauthorized"),
+            other => panic!("expected Unauthorized, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn long_retry_after_is_reported_instead_of_waited_out() {
+        let server = StubServer::start(vec![StubRoute::new("POST", "/v2/natural/nutrients", "rate_limited.json")
+            .with_status(429)
+            .with_header("Retry-After", "3600")]);
+
+        match provider_for(&server).parse("rice").await {
+            Err(QueryError::RateLimited { retry_after, message }) => {
+                assert_eq!(retry_after, Some(std::time::Duration::from_secs(3600)));
+                assert_eq!(message, "usage limits exceeded");
+            }
+            other => panic!("expected RateLimited, got {:?}", other),
+        }
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_after_may_be_a_date() {
+        let at = chrono::Utc::now() + chrono::Duration::hours(2);
+        let server = StubServer::start(vec![StubRoute::new("POST", "/v2/natural/nutrients", "rate_limited.json")
+            .with_status(429)
+            .with_header("Retry-After", &at.format("%a, %d %b %Y %H:%M:%S GMT").to_string())]);
+
+        match provider_for(&server).parse("rice").await {
+            Err(QueryError::RateLimited { retry_after: Some(wait), .. }) => {
+                assert!(wait.as_secs() > 7_100 && wait.as_secs() <= 7_200);
+            }
+            other => panic!("expected RateLimited with a wait, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn daily_quota_stops_requests_before_they_are_sent() {
+        let server = StubServer::nutritionix();
+        let provider = provider_for(&server).with_quota(DailyQuota::in_memory(1));
+
+        provider.parse("rice").await.unwrap();
+        assert!(matches!(provider.parse("rice").await, Err(QueryError::RateLimited { .. })));
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(provider.quota().map(|usage| usage.used), Some(1));
+    }
+
+    #[tokio::test]
+    async fn unreachable_server_is_offline() {
+        // Bind and drop a listener to get a port nothing is listening on.
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let provider = Nutritionix::new(stub_credentials())
+            .with_base_url(&format!("http://127.0.0.1:{}", port))
+            .with_quota(DailyQuota::in_memory(100));
+
+        assert!(matches!(provider.parse("rice").await, Err(QueryError::Offline(_))));
+        // Nothing reached the server, so none of the quota was spent.
+        assert_eq!(provider.quota().map(|usage| usage.used), Some(0));
+    }
+
+    // What AddFoods does: look the text up, add the results, read them back.
+    #[tokio::test]
+    async fn parsed_foods_are_stored_and_read_back() {
+        let server = StubServer::nutritionix();
+        let foods = provider_for(&server).parse("1 cup rice and 2 eggs").await.unwrap();
+
+        let mut repository = SqliteRepository::open(":memory:").unwrap();
+        repository.add_foods(foods, ConflictStrategy::Skip).unwrap();
+        let stored = repository.foods().unwrap();
+
+        // Stored per 100 g, keeping the 158 g the amounts were for as the serving.
+        let rice = stored.iter().find(|food| food.name == "rice").unwrap();
+        assert_eq!(rice.serving_grams, Some(158.0));
+        assert_eq!(rice.serving_label.as_deref(), Some("1 cup"));
+        // The "100 g" alternative is dropped; grams need no measure.
+        assert_eq!(rice.measures, vec![Measure { name: "cup".to_string(), grams: 158.0 }]);
+        let protein = rice.nutrient(NutrientKind::Protein).unwrap();
+        assert!((protein - 4.2502 * 100.0 / 158.0).abs() < 0.0001);
+        assert_eq!(rice.nutrient(NutrientKind::VitaminD), None);
+
+        // A serving that happens to weigh 100 g is still the serving.
+        let egg = stored.iter().find(|food| food.name == "egg").unwrap();
+        assert_eq!(egg.serving_grams, Some(100.0));
+        assert_eq!(to_grams(egg, "3 large"), Ok(150.0));
+        assert_eq!(egg.nutrient(NutrientKind::Cholesterol), Some(372.0));
+    }
+
+    // A scanned UPC-A is found again by the EAN-13 it's stored as.
+    #[tokio::test]
+    async fn barcode_lookups_are_found_locally_once_stored() {
+        let server = StubServer::nutritionix();
+        let food = provider_for(&server).lookup_barcode("0041196010022").await.unwrap();
+
+        let mut repository = SqliteRepository::open(":memory:").unwrap();
+        assert_eq!(repository.food_by_barcode("0041196010022").unwrap(), None);
+        repository.add_foods(vec![food], ConflictStrategy::Skip).unwrap();
+
+        let barcode = normalize_barcode("041196 010022").unwrap();
+        let stored = repository.food_by_barcode(&barcode).unwrap().unwrap();
+        assert_eq!(stored.name, "Jasmine Rice");
+        assert_eq!(stored.brand.as_deref(), Some("Mahatma"));
+    }
+}
@@ -0,0 +1,253 @@
+// Custom foods typed in from a nutrition facts label. The label is checked
+// for obvious typos and then turned into a ProviderFood, so it's stored the
+// same way as a food from a provider or dataset.
+
+use crate::data_entry::BASIS_GRAMS;
+use crate::measures::{parse_amount, Measure};
+use crate::nutrients::NutrientKind;
+use crate::provider::{AmountBasis, ProviderFood};
+
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+// The nutrients a US nutrition facts label lists, in label order.
+pub const LABEL_NUTRIENTS: &[NutrientKind] = &[
+    NutrientKind::Energy,
+    NutrientKind::TotalFat,
+    NutrientKind::SaturatedFat,
+    NutrientKind::TransFat,
+    NutrientKind::Cholesterol,
+    NutrientKind::Sodium,
+    NutrientKind::Carbohydrate,
+    NutrientKind::Fiber,
+    NutrientKind::TotalSugars,
+    NutrientKind::AddedSugars,
+    NutrientKind::Protein,
+    NutrientKind::VitaminD,
+    NutrientKind::Calcium,
+    NutrientKind::Iron,
+    NutrientKind::Potassium,
+];
+
+// Nutrients that are part of another, so can't exceed it.
+const PARTS: &[(NutrientKind, NutrientKind)] = &[
+    (NutrientKind::SaturatedFat, NutrientKind::TotalFat),
+    (NutrientKind::TransFat, NutrientKind::TotalFat),
+    (NutrientKind::Fiber, NutrientKind::Carbohydrate),
+    (NutrientKind::TotalSugars, NutrientKind::Carbohydrate),
+    (NutrientKind::AddedSugars, NutrientKind::TotalSugars),
+];
+
+// Atwater factors, kcal per gram.
+const KCAL_PER_GRAM: &[(NutrientKind, f32)] = &[
+    (NutrientKind::TotalFat, 9.0),
+    (NutrientKind::Carbohydrate, 4.0),
+    (NutrientKind::Protein, 4.0),
+];
+// Labels round every value, so calories are only questioned when they're
+// further than this fraction and this many kcal from what the macros imply.
+const ENERGY_TOLERANCE: f32 = 0.2;
+const ENERGY_SLACK_KCAL: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LabelBasis {
+    #[default]
+    PerServing,
+    Per100Grams,
+}
+
+impl LabelBasis {
+    pub fn all() -> &'static [LabelBasis] {
+        &[LabelBasis::PerServing, LabelBasis::Per100Grams]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LabelBasis::PerServing => "per serving",
+            LabelBasis::Per100Grams => "per 100 g",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NutritionLabel {
+    pub name: String,
+    pub brand: Option<String>,
+    // As printed, e.g. "2 tbsp".
+    pub serving_label: Option<String>,
+    pub serving_grams: Option<f32>,
+    pub basis: LabelBasis,
+    pub values: BTreeMap<NutrientKind, f32>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum LabelError {
+    #[error("A name is required")]
+    MissingName,
+    #[error("The serving size in grams is required for values per serving")]
+    MissingServing,
+    #[error("The serving size must be more than 0 g")]
+    InvalidServing,
+    #[error("{0} must be 0 or more")]
+    Negative(NutrientKind),
+    #[error("{part} can't be more than {whole}")]
+    PartExceedsWhole { part: NutrientKind, whole: NutrientKind },
+}
+
+impl NutritionLabel {
+    // Mistakes that make the label impossible.
+    pub fn validate(&self) -> Result<(), LabelError> {
+        if self.name.trim().is_empty() {
+            return Err(LabelError::MissingName);
+        }
+        match self.serving_grams {
+            Some(grams) if !grams.is_finite() || grams <= 0.0 => return Err(LabelError::InvalidServing),
+            None if self.basis == LabelBasis::PerServing => return Err(LabelError::MissingServing),
+            _ => {}
+        }
+        if let Some((kind, _)) = self.values.iter().find(|(_, value)| !value.is_finite() || **value < 0.0) {
+            return Err(LabelError::Negative(*kind));
+        }
+        for (part, whole) in PARTS {
+            if let (Some(part_value), Some(whole_value)) = (self.values.get(part), self.values.get(whole)) {
+                if part_value > whole_value {
+                    return Err(LabelError::PartExceedsWhole { part: *part, whole: *whole });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Things that look wrong but might be what's printed, for the user to confirm.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        if let (Some(stated), Some(implied)) = (self.values.get(&NutrientKind::Energy), self.macro_energy()) {
+            let difference = (stated - implied).abs();
+            if difference > ENERGY_SLACK_KCAL && difference > ENERGY_TOLERANCE * stated.max(implied) {
+                warnings.push(format!(
+                    "The label says {:.0} kcal, but its fat, carbohydrate and protein add up to about {:.0} kcal",
+                    stated, implied
+                ));
+            }
+        }
+
+        warnings
+    }
+
+    // Energy implied by whichever macros were entered, if any were.
+    fn macro_energy(&self) -> Option<f32> {
+        let mut entered = KCAL_PER_GRAM
+            .iter()
+            .filter_map(|(kind, kcal)| self.values.get(kind).map(|grams| grams * kcal))
+            .peekable();
+        entered.peek()?;
+        Some(entered.sum())
+    }
+
+    // Values per serving are stored against the serving weight, and values
+    // per 100 g against 100 g with the serving kept for reference, as with
+    // dataset imports. A serving like "2 tbsp" also becomes a measure.
+    pub fn to_food(&self) -> ProviderFood {
+        let (serving_weight_grams, basis) = match (self.basis, self.serving_grams) {
+            (LabelBasis::PerServing, Some(grams)) => (grams, AmountBasis::PerServing),
+            _ => (BASIS_GRAMS, AmountBasis::Per100Grams),
+        };
+        let measure = match (&self.serving_label, self.serving_grams) {
+            (Some(label), Some(grams)) => parse_amount(label)
+                .ok()
+                .and_then(|(quantity, unit)| Measure::new(&unit, quantity, grams)),
+            _ => None,
+        };
+
+        ProviderFood {
+            name: self.name.trim().to_string(),
+            brand: self.brand.clone(),
+            serving_weight_grams,
+            basis,
+            serving_label: self.serving_label.clone(),
+            serving_grams: self.serving_grams,
+            measures: measure.into_iter().collect(),
+            nutrients: self.values.iter().map(|(kind, value)| (*kind, *value)).collect(),
+            ..ProviderFood::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peanut_butter(values: &[(NutrientKind, f32)]) -> NutritionLabel {
+        NutritionLabel {
+            name: "Peanut butter".to_string(),
+            serving_label: Some("2 tbsp".to_string()),
+            serving_grams: Some(32.0),
+            values: values.iter().copied().collect(),
+            ..NutritionLabel::default()
+        }
+    }
+
+    #[test]
+    fn plausible_label_passes() {
+        let label = peanut_butter(&[
+            (NutrientKind::Energy, 190.0),
+            (NutrientKind::TotalFat, 16.0),
+            (NutrientKind::SaturatedFat, 3.0),
+            (NutrientKind::Carbohydrate, 7.0),
+            (NutrientKind::Protein, 7.0),
+        ]);
+
+        assert_eq!(label.validate(), Ok(()));
+        assert!(label.warnings().is_empty());
+
+        let food = label.to_food();
+        assert_eq!(food.serving_weight_grams, 32.0);
+        assert_eq!(food.nutrients.get(&NutrientKind::Protein), Some(&7.0));
+        assert_eq!(food.measures, vec![Measure { name: "tbsp".to_string(), grams: 16.0 }]);
+    }
+
+    #[test]
+    fn impossible_values_are_rejected() {
+        let label = peanut_butter(&[(NutrientKind::TotalFat, 3.0), (NutrientKind::SaturatedFat, 16.0)]);
+        assert_eq!(
+            label.validate(),
+            Err(LabelError::PartExceedsWhole {
+                part: NutrientKind::SaturatedFat,
+                whole: NutrientKind::TotalFat
+            })
+        );
+
+        let no_serving = NutritionLabel {
+            serving_grams: None,
+            ..peanut_butter(&[])
+        };
+        assert_eq!(no_serving.validate(), Err(LabelError::MissingServing));
+    }
+
+    #[test]
+    fn calories_far_from_the_macros_are_questioned() {
+        // 1900 kcal is a slipped decimal point.
+        let label = peanut_butter(&[
+            (NutrientKind::Energy, 1900.0),
+            (NutrientKind::TotalFat, 16.0),
+            (NutrientKind::Carbohydrate, 7.0),
+            (NutrientKind::Protein, 7.0),
+        ]);
+        assert_eq!(label.validate(), Ok(()));
+        assert_eq!(label.warnings().len(), 1);
+    }
+
+    #[test]
+    fn values_per_100_grams_keep_the_serving_for_reference() {
+        let label = NutritionLabel {
+            basis: LabelBasis::Per100Grams,
+            ..peanut_butter(&[(NutrientKind::Energy, 590.0)])
+        };
+
+        let food = label.to_food();
+        assert_eq!(food.serving_weight_grams, 100.0);
+        assert_eq!(food.serving_grams, Some(32.0));
+        assert_eq!(food.nutrients.get(&NutrientKind::Energy), Some(&590.0));
+    }
+}
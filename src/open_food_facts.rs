@@ -0,0 +1,272 @@
+// Reader for Open Food Facts data dumps (https://world.openfoodfacts.org/data):
+// the JSONL product export or the tab-separated CSV, either of them gzipped or
+// not. Nutriments are taken from the `_100g` fields, which OFF normalizes to
+// grams (kcal and kJ for energy) whatever the label said.
+
+use crate::data_entry::BASIS_GRAMS;
+use crate::import::{Batcher, ImportError};
+use crate::nutrients::{NutrientKind, Unit};
+use crate::provider::{AmountBasis, ProviderFood};
+
+use flate2::read::MultiGzDecoder;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+const VITAMIN_D_IU_PER_MICROGRAM: f32 = 40.0;
+
+// OFF nutriment names and the registry nutrient each one fills.
+const NUTRIMENTS: &[(&str, NutrientKind)] = &[
+    ("energy-kcal", NutrientKind::Energy),
+    ("energy-kj", NutrientKind::EnergyKj),
+    ("fat", NutrientKind::TotalFat),
+    ("saturated-fat", NutrientKind::SaturatedFat),
+    ("monounsaturated-fat", NutrientKind::MonounsaturatedFat),
+    ("polyunsaturated-fat", NutrientKind::PolyunsaturatedFat),
+    ("trans-fat", NutrientKind::TransFat),
+    ("cholesterol", NutrientKind::Cholesterol),
+    ("carbohydrates", NutrientKind::Carbohydrate),
+    ("sugars", NutrientKind::TotalSugars),
+    ("added-sugars", NutrientKind::AddedSugars),
+    ("sucrose", NutrientKind::Sucrose),
+    ("glucose", NutrientKind::Glucose),
+    ("fructose", NutrientKind::Fructose),
+    ("lactose", NutrientKind::Lactose),
+    ("maltose", NutrientKind::Maltose),
+    ("galactose", NutrientKind::Galactose),
+    ("starch", NutrientKind::Starch),
+    ("fiber", NutrientKind::Fiber),
+    ("proteins", NutrientKind::Protein),
+    ("sodium", NutrientKind::Sodium),
+    ("vitamin-a", NutrientKind::VitaminARae),
+    ("vitamin-d", NutrientKind::VitaminD),
+    ("vitamin-e", NutrientKind::VitaminE),
+    ("vitamin-k", NutrientKind::VitaminK),
+    ("vitamin-c", NutrientKind::VitaminC),
+    ("vitamin-b1", NutrientKind::Thiamin),
+    ("vitamin-b2", NutrientKind::Riboflavin),
+    ("vitamin-pp", NutrientKind::Niacin),
+    ("vitamin-b6", NutrientKind::VitaminB6),
+    ("vitamin-b9", NutrientKind::FolateTotal),
+    ("vitamin-b12", NutrientKind::VitaminB12),
+    ("pantothenic-acid", NutrientKind::PantothenicAcid),
+    ("choline", NutrientKind::Choline),
+    ("potassium", NutrientKind::Potassium),
+    ("calcium", NutrientKind::Calcium),
+    ("phosphorus", NutrientKind::Phosphorus),
+    ("iron", NutrientKind::Iron),
+    ("magnesium", NutrientKind::Magnesium),
+    ("zinc", NutrientKind::Zinc),
+    ("copper", NutrientKind::Copper),
+    ("manganese", NutrientKind::Manganese),
+    ("fluoride", NutrientKind::Fluoride),
+    ("selenium", NutrientKind::Selenium),
+    ("caffeine", NutrientKind::Caffeine),
+];
+
+pub fn read(path: &Path, batcher: &mut Batcher) -> Result<(), ImportError> {
+    let name = path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let file = File::open(path).map_err(|err| ImportError::io(path, err))?;
+
+    let input: Box<dyn Read> = if name.ends_with(".gz") {
+        Box::new(MultiGzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let input = BufReader::new(input);
+
+    if name.contains(".json") {
+        read_jsonl(input, path, batcher)
+    } else {
+        read_csv(input, batcher)
+    }
+}
+
+// Converts a `_100g` value from grams to the unit the registry stores.
+fn from_grams(kind: NutrientKind, value: f32) -> Option<f32> {
+    match kind.unit() {
+        Unit::Gram | Unit::Kilocalorie | Unit::Kilojoule => Some(value),
+        Unit::Milligram => Some(value * 1_000.0),
+        Unit::Microgram => Some(value * 1_000_000.0),
+        Unit::InternationalUnit if kind == NutrientKind::VitaminD => {
+            Some(value * 1_000_000.0 * VITAMIN_D_IU_PER_MICROGRAM)
+        }
+        Unit::InternationalUnit => None,
+    }
+}
+
+// Missing names and codes are common in the dumps; those products are skipped.
+fn new_product(
+    code: &str,
+    name: &str,
+    brands: &str,
+    serving_size: &str,
+    serving_quantity: Option<f32>,
+) -> Option<ProviderFood> {
+    let name = name.trim();
+    if name.is_empty() || code.trim().is_empty() {
+        return None;
+    }
+
+    Some(ProviderFood {
+        name: name.to_string(),
+        // Only the first of a comma-separated list like "Nestlé, KitKat".
+        brand: brands.split(',').map(str::trim).find(|brand| !brand.is_empty()).map(str::to_string),
+        barcode: Some(code.trim().to_string()),
+        serving_weight_grams: BASIS_GRAMS,
+        basis: AmountBasis::Per100Grams,
+        serving_label: Some(serving_size.trim().to_string()).filter(|label| !label.is_empty()),
+        serving_grams: serving_quantity.filter(|grams| *grams > 0.0),
+        ..ProviderFood::default()
+    })
+}
+
+// OFF is loose about types: numbers sometimes arrive as strings.
+fn as_number(value: &Value) -> Option<f32> {
+    match value {
+        Value::Number(number) => number.as_f64().map(|number| number as f32),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_text(value: &Value) -> &str {
+    value.as_str().unwrap_or_default()
+}
+
+#[derive(Deserialize)]
+struct JsonProduct {
+    #[serde(default)]
+    code: Value,
+    #[serde(default)]
+    product_name: Value,
+    #[serde(default)]
+    brands: Value,
+    #[serde(default)]
+    serving_size: Value,
+    #[serde(default)]
+    serving_quantity: Value,
+    #[serde(default)]
+    nutriments: HashMap<String, Value>,
+}
+
+fn read_jsonl(input: impl BufRead, path: &Path, batcher: &mut Batcher) -> Result<(), ImportError> {
+    for line in input.lines() {
+        let line = line.map_err(|err| ImportError::io(path, err))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let product: JsonProduct = serde_json::from_str(&line)?;
+        let code = match &product.code {
+            Value::Number(number) => number.to_string(),
+            other => as_text(other).to_string(),
+        };
+        let found = new_product(
+            &code,
+            as_text(&product.product_name),
+            as_text(&product.brands),
+            as_text(&product.serving_size),
+            as_number(&product.serving_quantity),
+        );
+
+        if let Some(mut food) = found {
+            for (name, kind) in NUTRIMENTS {
+                let value = product.nutriments.get(&format!("{}_100g", name)).and_then(as_number);
+                if let Some(value) = value.and_then(|value| from_grams(*kind, value)) {
+                    food.nutrients.insert(*kind, value);
+                }
+            }
+            batcher.push(food)?;
+        }
+    }
+
+    Ok(())
+}
+
+// The CSV export is tab-separated and doesn't quote fields, so quotes inside
+// product names must be taken literally.
+fn read_csv(input: impl Read, batcher: &mut Batcher) -> Result<(), ImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .quoting(false)
+        .flexible(true)
+        .from_reader(input);
+
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header == name);
+    let (code, name) = match (column("code"), column("product_name")) {
+        (Some(code), Some(name)) => (code, name),
+        _ => {
+            return Err(ImportError::Format(
+                "Not an Open Food Facts CSV export: no code or product_name column".to_string(),
+            ))
+        }
+    };
+    let brands = column("brands");
+    let serving_size = column("serving_size");
+    let serving_quantity = column("serving_quantity");
+    let nutriments: Vec<(usize, NutrientKind)> = NUTRIMENTS
+        .iter()
+        .filter_map(|(name, kind)| column(&format!("{}_100g", name)).map(|index| (index, *kind)))
+        .collect();
+
+    for record in reader.records() {
+        let record = record?;
+        let field = |index: Option<usize>| index.and_then(|index| record.get(index)).unwrap_or_default();
+
+        let found = new_product(
+            field(Some(code)),
+            field(Some(name)),
+            field(brands),
+            field(serving_size),
+            field(serving_quantity).trim().parse().ok(),
+        );
+
+        if let Some(mut food) = found {
+            for (index, kind) in &nutriments {
+                let value = field(Some(*index)).trim().parse::<f32>().ok();
+                if let Some(value) = value.and_then(|value| from_grams(*kind, value)) {
+                    food.nutrients.insert(*kind, value);
+                }
+            }
+            batcher.push(food)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::read_all;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/open_food_facts").join(name)
+    }
+
+    #[test]
+    fn jsonl_and_csv_exports_agree() {
+        let from_jsonl = read_all(|batcher| read(&fixture("products.jsonl"), batcher)).unwrap();
+        let from_csv = read_all(|batcher| read(&fixture("products.csv"), batcher)).unwrap();
+
+        // The product without a name is skipped in both.
+        assert_eq!(from_jsonl.len(), 2);
+        assert_eq!(from_jsonl, from_csv);
+
+        let spread = &from_jsonl[0];
+        assert_eq!(spread.name, "Nutella");
+        assert_eq!(spread.brand.as_deref(), Some("Ferrero"));
+        assert_eq!(spread.barcode.as_deref(), Some("3017620422003"));
+        assert_eq!(spread.serving_grams, Some(15.0));
+        assert_eq!(spread.nutrients.get(&NutrientKind::Energy), Some(&539.0));
+        // Sodium is given in grams and stored in milligrams.
+        let sodium = spread.nutrients[&NutrientKind::Sodium];
+        assert!((sodium - 42.8).abs() < 0.001);
+    }
+}
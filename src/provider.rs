@@ -0,0 +1,185 @@
+use crate::http::QuotaUsage;
+use crate::measures::Measure;
+use crate::nutrients::NutrientKind;
+
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum QueryError {
+    #[error("Could not reach the server: {0}")]
+    Offline(String),
+    #[error("Network request failed: {0}")]
+    Network(reqwest::Error),
+    #[error("No results found for the given query")]
+    NotFound,
+    #[error("Not authorized: {0}")]
+    Unauthorized(String),
+    #[error("Rate limited{}: {message}", retry_hint(.retry_after))]
+    RateLimited {
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    #[error("Server error ({status}): {message}")]
+    Server { status: u16, message: String },
+    #[error("Could not understand the response: {0}")]
+    Decode(String),
+    #[error("{0} does not support this kind of lookup")]
+    Unsupported(&'static str),
+}
+
+impl QueryError {
+    // Maps an unsuccessful HTTP status to an error, keeping whatever message
+    // the server sent back.
+    pub fn from_status(status: StatusCode, message: String, retry_after: Option<Duration>) -> QueryError {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => QueryError::Unauthorized(message),
+            StatusCode::NOT_FOUND => QueryError::NotFound,
+            StatusCode::TOO_MANY_REQUESTS => QueryError::RateLimited { retry_after, message },
+            status if status.is_server_error() => QueryError::Server {
+                status: status.as_u16(),
+                message,
+            },
+            _ => QueryError::BadRequest(message),
+        }
+    }
+
+    // Whether trying the same request again later could succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            QueryError::Offline(_)
+                | QueryError::Network(_)
+                | QueryError::RateLimited { .. }
+                | QueryError::Server { .. }
+        )
+    }
+}
+
+impl From<reqwest::Error> for QueryError {
+    fn from(err: reqwest::Error) -> QueryError {
+        if err.is_connect() {
+            QueryError::Offline(err.to_string())
+        } else if err.is_decode() {
+            QueryError::Decode(err.to_string())
+        } else {
+            QueryError::Network(err)
+        }
+    }
+}
+
+fn retry_hint(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(wait) => format!(" (try again in {}s)", wait.as_secs()),
+        None => String::new(),
+    }
+}
+
+// What a source's amounts are for.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub enum AmountBasis {
+    // The serving it describes, which is then the food's typical serving.
+    // Responses cached before sources said otherwise were all per serving.
+    #[default]
+    PerServing,
+    // 100 g, whatever serving it names.
+    Per100Grams,
+}
+
+// A food as any provider reports it, before it is stored. Amounts are for
+// `serving_weight_grams` of the food.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct ProviderFood {
+    pub name: String,
+    pub brand: Option<String>,
+    pub barcode: Option<String>,
+    pub serving_weight_grams: f32,
+    #[serde(default)]
+    pub basis: AmountBasis,
+    // A typical serving, for sources whose amounts are per 100 g rather than
+    // per serving.
+    pub serving_label: Option<String>,
+    pub serving_grams: Option<f32>,
+    // Household measures the source gave, such as "slice" or "cup".
+    #[serde(default)]
+    pub measures: Vec<Measure>,
+    // Only what the provider reported; anything absent is unknown.
+    pub nutrients: HashMap<NutrientKind, f32>,
+    // Values reported under USDA nutrient numbers the registry doesn't know.
+    pub other_nutrients: HashMap<u32, f32>,
+}
+
+impl ProviderFood {
+    // Files an amount reported under a USDA nutrient number.
+    pub fn insert_usda(&mut self, attr_id: u32, value: f32) {
+        match NutrientKind::from_attr_id(attr_id) {
+            Some(kind) => {
+                self.nutrients.insert(kind, value);
+            }
+            None => {
+                self.other_nutrients.insert(attr_id, value);
+            }
+        }
+    }
+}
+
+// UPC-A, EAN-13 and GTIN-14 codes for the same product differ only in
+// leading zeros, so they're stored as 13 digits (8 for EAN-8) to match
+// however the code was typed, scanned or imported.
+pub fn normalize_barcode(code: &str) -> Option<String> {
+    let digits: String = code.chars().filter(|c| c.is_ascii_digit()).collect();
+
+    match digits.len() {
+        8 | 13 => Some(digits),
+        12 => Some(format!("0{}", digits)),
+        14 if digits.starts_with('0') => Some(digits[1..].to_string()),
+        _ => None,
+    }
+}
+
+// One candidate from a search, cheap enough to list before choosing.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SearchHit {
+    // Opaque to everything except the provider that issued it.
+    pub id: String,
+    pub name: String,
+    pub brand: Option<String>,
+    // A packaged product rather than a generic food.
+    pub branded: bool,
+    // The provider's usual serving, e.g. "1 cup cooked".
+    pub serving: Option<String>,
+    pub calories: Option<f32>,
+}
+
+#[async_trait(?Send)]
+pub trait NutritionProvider {
+    fn name(&self) -> &'static str;
+
+    // Today's request count against the provider's allowance, if it has one.
+    fn quota(&self) -> Option<QuotaUsage> {
+        None
+    }
+
+    async fn search(&self, _query: &str) -> Result<Vec<SearchHit>, QueryError> {
+        Err(QueryError::Unsupported(self.name()))
+    }
+
+    async fn lookup(&self, _id: &str) -> Result<ProviderFood, QueryError> {
+        Err(QueryError::Unsupported(self.name()))
+    }
+
+    // Free text such as "2 eggs and a slice of toast" into one food per item.
+    async fn parse(&self, _text: &str) -> Result<Vec<ProviderFood>, QueryError> {
+        Err(QueryError::Unsupported(self.name()))
+    }
+
+    async fn lookup_barcode(&self, _barcode: &str) -> Result<ProviderFood, QueryError> {
+        Err(QueryError::Unsupported(self.name()))
+    }
+}
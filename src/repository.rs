@@ -0,0 +1,1020 @@
+use crate::data_entry::{
+    self, total_nutrients, Database, Entry, Food, NutrientTotal, PantryItem, Recipe,
+};
+use crate::nutrients::NutrientKind;
+use crate::lookup::{normalize_query, CachedLookup, PendingLookup};
+use crate::measures::{to_grams, MeasureError};
+use crate::food_search;
+use crate::provider::ProviderFood;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use rusqlite::{Error as RusqliteError, ErrorCode};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("Database error: {0}")]
+    Database(rusqlite::Error),
+    #[error("No food named \"{0}\"")]
+    FoodNotFound(String),
+    #[error("\"{0}\" already exists")]
+    Duplicate(String),
+    #[error("\"{name}\" is still used by {references}")]
+    InUse { name: String, references: FoodReferences },
+    #[error("{0}")]
+    Measure(#[from] MeasureError),
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(err: rusqlite::Error) -> StorageError {
+        match &err {
+            RusqliteError::SqliteFailure(failure, Some(message))
+                if failure.code == ErrorCode::ConstraintViolation && message.contains("UNIQUE") =>
+            {
+                StorageError::Duplicate(message.clone())
+            }
+            _ => StorageError::Database(err),
+        }
+    }
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+// Where a food is used, which has to be dealt with before it can be deleted.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FoodReferences {
+    pub pantry: usize,
+    pub recipes: usize,
+    pub entries: usize,
+}
+
+impl FoodReferences {
+    pub fn is_empty(&self) -> bool {
+        self.pantry + self.recipes + self.entries == 0
+    }
+}
+
+impl fmt::Display for FoodReferences {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts = [
+            (self.pantry, "pantry item", "pantry items"),
+            (self.recipes, "recipe", "recipes"),
+            (self.entries, "entry", "entries"),
+        ];
+        let parts: Vec<String> = counts
+            .iter()
+            .filter(|(count, _, _)| *count > 0)
+            .map(|(count, one, many)| format!("{} {}", count, if *count == 1 { one } else { many }))
+            .collect();
+
+        match parts.split_last() {
+            None => f.write_str("nothing"),
+            Some((last, [])) => f.write_str(last),
+            Some((last, rest)) => write!(f, "{} and {}", rest.join(", "), last),
+        }
+    }
+}
+
+// What deleting a food does to the pantry items, recipes and entries using it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReferenceHandling {
+    // Fail with InUse if anything uses the food.
+    Refuse,
+    // Remove the food from all of them, deleting its pantry items.
+    Cascade,
+    // Point them at the named food instead. Where a recipe or entry already
+    // has that food, the weights are added together.
+    ReassignTo(String),
+}
+
+// What to do with a food whose name is already taken.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ConflictStrategy {
+    #[default]
+    Skip,
+    // Replace the stored food's details and nutrients. It keeps its id, so
+    // entries and recipes that use it see the new values.
+    Overwrite,
+    // Store the new food as "name (2)", "name (3)" and so on.
+    KeepBoth,
+    // Fill in only what the stored food is missing.
+    Merge,
+}
+
+impl ConflictStrategy {
+    pub fn all() -> &'static [ConflictStrategy] {
+        &[
+            ConflictStrategy::Skip,
+            ConflictStrategy::Overwrite,
+            ConflictStrategy::KeepBoth,
+            ConflictStrategy::Merge,
+        ]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ConflictStrategy::Skip => "Skip it",
+            ConflictStrategy::Overwrite => "Overwrite the stored food",
+            ConflictStrategy::KeepBoth => "Keep both, numbering the new name",
+            ConflictStrategy::Merge => "Merge, filling in what the stored food is missing",
+        }
+    }
+}
+
+// What happened to one food passed to add_foods.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddOutcome {
+    Added,
+    // Added under this name because the original was taken.
+    Renamed(String),
+    Skipped,
+    Overwritten,
+    Merged,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub renamed: usize,
+    // Already stored under the same name.
+    pub skipped: usize,
+    pub overwritten: usize,
+    pub merged: usize,
+}
+
+impl ImportReport {
+    pub fn from_outcomes(outcomes: &[AddOutcome]) -> ImportReport {
+        let mut report = ImportReport::default();
+        for outcome in outcomes {
+            match outcome {
+                AddOutcome::Added => report.imported += 1,
+                AddOutcome::Renamed(_) => report.renamed += 1,
+                AddOutcome::Skipped => report.skipped += 1,
+                AddOutcome::Overwritten => report.overwritten += 1,
+                AddOutcome::Merged => report.merged += 1,
+            }
+        }
+        report
+    }
+}
+
+impl std::ops::AddAssign for ImportReport {
+    fn add_assign(&mut self, other: ImportReport) {
+        self.imported += other.imported;
+        self.renamed += other.renamed;
+        self.skipped += other.skipped;
+        self.overwritten += other.overwritten;
+        self.merged += other.merged;
+    }
+}
+
+pub trait FoodRepository {
+    fn foods(&self) -> StorageResult<Vec<Food>>;
+    // Adds the whole batch or none of it, settling names that are already
+    // taken with `on_conflict`. Returns an outcome per food, in order.
+    fn add_foods(&mut self, foods: Vec<ProviderFood>, on_conflict: ConflictStrategy) -> StorageResult<Vec<AddOutcome>>;
+    // `barcode` as given by normalize_barcode.
+    fn food_by_barcode(&self, barcode: &str) -> StorageResult<Option<Food>>;
+    fn food_named(&self, name: &str) -> StorageResult<Option<Food>>;
+    // Up to `limit` foods whose name, aliases or brand match `query`, allowing
+    // for unfinished words and typos, best first.
+    fn search_foods(&self, query: &str, limit: usize) -> StorageResult<Vec<Food>>;
+    // Replaces the food called `name` with `food`, which may rename it. A
+    // nutrient set to None is no longer reported.
+    fn update_food(&mut self, name: &str, food: &Food) -> StorageResult<()>;
+    fn food_references(&self, name: &str) -> StorageResult<FoodReferences>;
+    fn delete_food(&mut self, name: &str, references: ReferenceHandling) -> StorageResult<()>;
+
+    fn add_food(&mut self, food: ProviderFood, on_conflict: ConflictStrategy) -> StorageResult<AddOutcome> {
+        // One outcome per food, so there's exactly one here.
+        Ok(self.add_foods(vec![food], on_conflict)?.remove(0))
+    }
+
+    fn import_foods(&mut self, foods: Vec<ProviderFood>, on_conflict: ConflictStrategy) -> StorageResult<ImportReport> {
+        Ok(ImportReport::from_outcomes(&self.add_foods(foods, on_conflict)?))
+    }
+}
+
+// Foods are referred to by name, which is unique in every backend.
+pub trait JournalRepository {
+    fn pantry(&self) -> StorageResult<Vec<PantryItem>>;
+    fn add_pantry_item(&mut self, food_name: &str, weight_grams: f32) -> StorageResult<()>;
+
+    fn recipes(&self) -> StorageResult<Vec<Recipe>>;
+    fn add_recipe(&mut self, name: &str, ingredients: &[(String, f32)]) -> StorageResult<()>;
+
+    fn entries(&self) -> StorageResult<Vec<Entry>>;
+    fn add_entry(&mut self, timestamp: NaiveDateTime, foods: &[(String, f32)]) -> StorageResult<()>;
+
+    fn daily_totals(&self, date: NaiveDate) -> StorageResult<BTreeMap<NutrientKind, NutrientTotal>> {
+        let entries: Vec<Entry> = self
+            .entries()?
+            .into_iter()
+            .filter(|entry| entry.timestamp.date() == date)
+            .collect();
+
+        Ok(total_nutrients(entries.iter().flat_map(|entry| {
+            entry.foods.iter().zip(entry.weights.iter().copied())
+        })))
+    }
+}
+
+// Provider responses remembered by provider name and normalized query.
+pub trait LookupCache {
+    fn cached_lookup(&self, provider: &str, query: &str) -> StorageResult<Option<CachedLookup>>;
+    fn cache_lookup(
+        &mut self,
+        provider: &str,
+        query: &str,
+        foods: &[ProviderFood],
+        fetched_at: NaiveDateTime,
+    ) -> StorageResult<()>;
+}
+
+// Queries waiting for the provider to become reachable again.
+pub trait LookupQueue {
+    fn pending_lookups(&self) -> StorageResult<Vec<PendingLookup>>;
+    // Each query is queued once per provider; queueing it again returns the
+    // id of the lookup already waiting.
+    fn queue_lookup(&mut self, provider: &str, query: &str, queued_at: NaiveDateTime) -> StorageResult<i64>;
+    // Logs an empty entry that the lookup's foods are added to once it resolves.
+    fn log_pending_entry(&mut self, lookup_id: i64, timestamp: NaiveDateTime) -> StorageResult<()>;
+    fn resolve_lookup(&mut self, lookup_id: i64, foods: &[ProviderFood]) -> StorageResult<()>;
+    fn record_lookup_failure(&mut self, lookup_id: i64, error: &str, stalled: bool) -> StorageResult<()>;
+    fn discard_lookup(&mut self, lookup_id: i64) -> StorageResult<()>;
+}
+
+// Everything the UI needs from a backend.
+pub trait Storage: FoodRepository + JournalRepository + LookupCache + LookupQueue {
+    // Where the data lives, for display in Settings.
+    fn location(&self) -> String;
+
+    // Logs an entry from amounts as typed, such as ("bread", "2 slices"),
+    // each converted to grams with the food's measures.
+    fn log_entry(&mut self, timestamp: NaiveDateTime, portions: &[(String, String)]) -> StorageResult<()> {
+        let mut foods = Vec::with_capacity(portions.len());
+        for (name, amount) in portions {
+            let food = self.food_named(name)?.ok_or_else(|| StorageError::FoodNotFound(name.clone()))?;
+            foods.push((name.clone(), to_grams(&food, amount)?));
+        }
+        self.add_entry(timestamp, &foods)
+    }
+}
+
+pub struct SqliteRepository {
+    db: Database,
+}
+
+impl SqliteRepository {
+    pub fn open(path: impl AsRef<Path>) -> Result<SqliteRepository, Box<dyn Error>> {
+        Ok(SqliteRepository { db: Database::open(path)? })
+    }
+
+    fn food_id(&self, name: &str) -> StorageResult<i64> {
+        data_entry::find_food_id(&self.db.conn, name)?.ok_or_else(|| StorageError::FoodNotFound(name.to_string()))
+    }
+
+    fn food_ids(&self, foods: &[(String, f32)]) -> StorageResult<Vec<(i64, f32)>> {
+        foods
+            .iter()
+            .map(|(name, weight_grams)| match data_entry::find_food_id(&self.db.conn, name)? {
+                Some(id) => Ok((id, *weight_grams)),
+                None => Err(StorageError::FoodNotFound(name.clone())),
+            })
+            .collect()
+    }
+}
+
+impl FoodRepository for SqliteRepository {
+    fn foods(&self) -> StorageResult<Vec<Food>> {
+        Ok(data_entry::get_foods(&self.db.conn)?)
+    }
+
+    fn add_foods(&mut self, foods: Vec<ProviderFood>, on_conflict: ConflictStrategy) -> StorageResult<Vec<AddOutcome>> {
+        Ok(data_entry::add_food_items(&mut self.db.conn, &foods, on_conflict)?)
+    }
+
+    fn food_by_barcode(&self, barcode: &str) -> StorageResult<Option<Food>> {
+        Ok(data_entry::find_food_by_barcode(&self.db.conn, barcode)?)
+    }
+
+    fn food_named(&self, name: &str) -> StorageResult<Option<Food>> {
+        Ok(data_entry::find_food(&self.db.conn, name)?)
+    }
+
+    fn search_foods(&self, query: &str, limit: usize) -> StorageResult<Vec<Food>> {
+        Ok(data_entry::search_foods(&self.db.conn, query, limit)?)
+    }
+
+    fn update_food(&mut self, name: &str, food: &Food) -> StorageResult<()> {
+        let food_id = self.food_id(name)?;
+        data_entry::update_food_item(&mut self.db.conn, food_id, food).map_err(|err| match StorageError::from(err) {
+            StorageError::Duplicate(_) => StorageError::Duplicate(food.name.clone()),
+            other => other,
+        })
+    }
+
+    fn food_references(&self, name: &str) -> StorageResult<FoodReferences> {
+        let food_id = self.food_id(name)?;
+        let (pantry, recipes, entries) = data_entry::count_food_references(&self.db.conn, food_id)?;
+        Ok(FoodReferences { pantry, recipes, entries })
+    }
+
+    fn delete_food(&mut self, name: &str, references: ReferenceHandling) -> StorageResult<()> {
+        let food_id = self.food_id(name)?;
+
+        let replacement = match &references {
+            ReferenceHandling::ReassignTo(target) if target != name => Some(self.food_id(target)?),
+            ReferenceHandling::Cascade => None,
+            _ => {
+                let used = self.food_references(name)?;
+                if !used.is_empty() {
+                    return Err(StorageError::InUse { name: name.to_string(), references: used });
+                }
+                None
+            }
+        };
+
+        Ok(data_entry::delete_food_item(&mut self.db.conn, food_id, replacement)?)
+    }
+}
+
+impl JournalRepository for SqliteRepository {
+    fn pantry(&self) -> StorageResult<Vec<PantryItem>> {
+        Ok(data_entry::get_pantry(&self.db.conn)?)
+    }
+
+    fn add_pantry_item(&mut self, food_name: &str, weight_grams: f32) -> StorageResult<()> {
+        match data_entry::find_food_id(&self.db.conn, food_name)? {
+            Some(food_id) => Ok(data_entry::insert_pantry_item(&self.db.conn, food_id, weight_grams)?),
+            None => Err(StorageError::FoodNotFound(food_name.to_string())),
+        }
+    }
+
+    fn recipes(&self) -> StorageResult<Vec<Recipe>> {
+        Ok(data_entry::get_recipes(&self.db.conn)?)
+    }
+
+    fn add_recipe(&mut self, name: &str, ingredients: &[(String, f32)]) -> StorageResult<()> {
+        let ingredients = self.food_ids(ingredients)?;
+        Ok(data_entry::insert_recipe(&mut self.db.conn, name, &ingredients)?)
+    }
+
+    fn entries(&self) -> StorageResult<Vec<Entry>> {
+        Ok(data_entry::get_entries(&self.db.conn)?)
+    }
+
+    fn add_entry(&mut self, timestamp: NaiveDateTime, foods: &[(String, f32)]) -> StorageResult<()> {
+        let foods = self.food_ids(foods)?;
+        Ok(data_entry::insert_entry(&mut self.db.conn, timestamp, &foods)?)
+    }
+
+    fn daily_totals(&self, date: NaiveDate) -> StorageResult<BTreeMap<NutrientKind, NutrientTotal>> {
+        Ok(data_entry::get_daily_totals(&self.db.conn, date)?)
+    }
+}
+
+impl LookupCache for SqliteRepository {
+    fn cached_lookup(&self, provider: &str, query: &str) -> StorageResult<Option<CachedLookup>> {
+        Ok(data_entry::get_cached_lookup(&self.db.conn, provider, query)?)
+    }
+
+    fn cache_lookup(
+        &mut self,
+        provider: &str,
+        query: &str,
+        foods: &[ProviderFood],
+        fetched_at: NaiveDateTime,
+    ) -> StorageResult<()> {
+        Ok(data_entry::cache_lookup(&self.db.conn, provider, query, foods, fetched_at)?)
+    }
+}
+
+impl LookupQueue for SqliteRepository {
+    fn pending_lookups(&self) -> StorageResult<Vec<PendingLookup>> {
+        Ok(data_entry::get_pending_lookups(&self.db.conn)?)
+    }
+
+    fn queue_lookup(&mut self, provider: &str, query: &str, queued_at: NaiveDateTime) -> StorageResult<i64> {
+        Ok(data_entry::queue_lookup(&self.db.conn, provider, query, queued_at)?)
+    }
+
+    fn log_pending_entry(&mut self, lookup_id: i64, timestamp: NaiveDateTime) -> StorageResult<()> {
+        Ok(data_entry::log_pending_entry(&mut self.db.conn, lookup_id, timestamp)?)
+    }
+
+    fn resolve_lookup(&mut self, lookup_id: i64, foods: &[ProviderFood]) -> StorageResult<()> {
+        Ok(data_entry::resolve_lookup(&mut self.db.conn, lookup_id, foods)?)
+    }
+
+    fn record_lookup_failure(&mut self, lookup_id: i64, error: &str, stalled: bool) -> StorageResult<()> {
+        Ok(data_entry::record_lookup_failure(&self.db.conn, lookup_id, error, stalled)?)
+    }
+
+    fn discard_lookup(&mut self, lookup_id: i64) -> StorageResult<()> {
+        Ok(data_entry::discard_lookup(&mut self.db.conn, lookup_id)?)
+    }
+}
+
+impl Storage for SqliteRepository {
+    fn location(&self) -> String {
+        self.db.path.display().to_string()
+    }
+}
+
+// Keeps everything in plain vectors; nothing survives the process.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    foods: Vec<Food>,
+    pantry: Vec<PantryItem>,
+    recipes: Vec<Recipe>,
+    entries: Vec<Entry>,
+    lookups: HashMap<(String, String), CachedLookup>,
+    // Each queued lookup with the index of its placeholder in `entries`.
+    pending: Vec<(PendingLookup, Option<usize>)>,
+    next_lookup_id: i64,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> InMemoryRepository {
+        InMemoryRepository::default()
+    }
+
+    fn food(&self, name: &str) -> StorageResult<&Food> {
+        self.foods
+            .iter()
+            .find(|food| food.name == name)
+            .ok_or_else(|| StorageError::FoodNotFound(name.to_string()))
+    }
+
+    // Pantry items, recipes and entries hold copies of the food, so every
+    // change to one has to reach those too.
+    fn replace_copies(&mut self, name: &str, food: &Food) {
+        let copies = self
+            .foods
+            .iter_mut()
+            .chain(self.pantry.iter_mut().map(|item| &mut item.food))
+            .chain(self.recipes.iter_mut().flat_map(|recipe| recipe.foods.iter_mut()))
+            .chain(self.entries.iter_mut().flat_map(|entry| entry.foods.iter_mut()));
+        for copy in copies.filter(|copy| copy.name == name) {
+            *copy = food.clone();
+        }
+    }
+
+    fn portions(&self, foods: &[(String, f32)]) -> StorageResult<(Vec<Food>, Vec<f32>)> {
+        let mut resolved = Vec::new();
+        let mut weights = Vec::new();
+
+        for (name, weight_grams) in foods {
+            resolved.push(self.food(name)?.clone());
+            weights.push(*weight_grams);
+        }
+
+        Ok((resolved, weights))
+    }
+}
+
+impl FoodRepository for InMemoryRepository {
+    fn foods(&self) -> StorageResult<Vec<Food>> {
+        Ok(self.foods.clone())
+    }
+
+    // Nothing here can fail partway, so the batch is trivially all or nothing.
+    fn add_foods(&mut self, foods: Vec<ProviderFood>, on_conflict: ConflictStrategy) -> StorageResult<Vec<AddOutcome>> {
+        let mut outcomes = Vec::with_capacity(foods.len());
+
+        for food in &foods {
+            let existing = self.foods.iter().position(|stored| stored.name == food.name);
+            let outcome = match (existing, on_conflict) {
+                (None, _) => {
+                    self.foods.push(Food::from(food));
+                    AddOutcome::Added
+                }
+                (Some(_), ConflictStrategy::Skip) => AddOutcome::Skipped,
+                // Aliases are the user's, not the source's, so they stay.
+                (Some(index), ConflictStrategy::Overwrite) => {
+                    let overwritten = Food {
+                        aliases: self.foods[index].aliases.clone(),
+                        ..Food::from(food)
+                    };
+                    self.replace_copies(&food.name, &overwritten);
+                    AddOutcome::Overwritten
+                }
+                (Some(index), ConflictStrategy::Merge) => {
+                    let mut merged = self.foods[index].clone();
+                    merged.merge(food);
+                    self.replace_copies(&food.name, &merged);
+                    AddOutcome::Merged
+                }
+                (Some(_), ConflictStrategy::KeepBoth) => {
+                    let name = (2..)
+                        .map(|number| format!("{} ({})", food.name, number))
+                        .find(|name| self.food(name).is_err())
+                        .expect("some number is free");
+                    self.foods.push(Food {
+                        name: name.clone(),
+                        ..Food::from(food)
+                    });
+                    AddOutcome::Renamed(name)
+                }
+            };
+            outcomes.push(outcome);
+        }
+
+        Ok(outcomes)
+    }
+
+    fn food_by_barcode(&self, barcode: &str) -> StorageResult<Option<Food>> {
+        Ok(self.foods.iter().find(|food| food.barcode.as_deref() == Some(barcode)).cloned())
+    }
+
+    fn food_named(&self, name: &str) -> StorageResult<Option<Food>> {
+        Ok(self.food(name).ok().cloned())
+    }
+
+    fn search_foods(&self, query: &str, limit: usize) -> StorageResult<Vec<Food>> {
+        Ok(food_search::rank(self.foods.clone(), query, limit))
+    }
+
+    fn update_food(&mut self, name: &str, food: &Food) -> StorageResult<()> {
+        self.food(name)?;
+        if food.name != name && self.food(&food.name).is_ok() {
+            return Err(StorageError::Duplicate(food.name.clone()));
+        }
+
+        self.replace_copies(name, food);
+        Ok(())
+    }
+
+    fn food_references(&self, name: &str) -> StorageResult<FoodReferences> {
+        self.food(name)?;
+        Ok(FoodReferences {
+            pantry: self.pantry.iter().filter(|item| item.food.name == name).count(),
+            recipes: self.recipes.iter().filter(|recipe| recipe.foods.iter().any(|food| food.name == name)).count(),
+            entries: self.entries.iter().filter(|entry| entry.foods.iter().any(|food| food.name == name)).count(),
+        })
+    }
+
+    fn delete_food(&mut self, name: &str, references: ReferenceHandling) -> StorageResult<()> {
+        self.food(name)?;
+
+        match references {
+            ReferenceHandling::ReassignTo(target) if target != name => {
+                let replacement = self.food(&target)?.clone();
+                for item in self.pantry.iter_mut().filter(|item| item.food.name == name) {
+                    item.food = replacement.clone();
+                }
+                for recipe in &mut self.recipes {
+                    reassign_portions(&mut recipe.foods, &mut recipe.weights, name, &replacement);
+                }
+                for entry in &mut self.entries {
+                    reassign_portions(&mut entry.foods, &mut entry.weights, name, &replacement);
+                }
+            }
+            ReferenceHandling::Cascade => {
+                self.pantry.retain(|item| item.food.name != name);
+                for recipe in &mut self.recipes {
+                    remove_portions(&mut recipe.foods, &mut recipe.weights, name);
+                }
+                for entry in &mut self.entries {
+                    remove_portions(&mut entry.foods, &mut entry.weights, name);
+                }
+            }
+            _ => {
+                let used = self.food_references(name)?;
+                if !used.is_empty() {
+                    return Err(StorageError::InUse { name: name.to_string(), references: used });
+                }
+            }
+        }
+
+        self.foods.retain(|food| food.name != name);
+        Ok(())
+    }
+}
+
+// Recipes and entries keep foods and weights in parallel vectors.
+fn remove_portions(foods: &mut Vec<Food>, weights: &mut Vec<f32>, name: &str) {
+    while let Some(index) = foods.iter().position(|food| food.name == name) {
+        foods.remove(index);
+        weights.remove(index);
+    }
+}
+
+// Every portion of `name` is moved, merging into the replacement's portion
+// where there is one.
+fn reassign_portions(foods: &mut Vec<Food>, weights: &mut Vec<f32>, name: &str, replacement: &Food) {
+    while let Some(index) = foods.iter().position(|food| food.name == name) {
+        match foods.iter().position(|food| food.name == replacement.name) {
+            Some(existing) => {
+                weights[existing] += weights[index];
+                foods.remove(index);
+                weights.remove(index);
+            }
+            None => foods[index] = replacement.clone(),
+        }
+    }
+}
+
+impl JournalRepository for InMemoryRepository {
+    fn pantry(&self) -> StorageResult<Vec<PantryItem>> {
+        Ok(self.pantry.clone())
+    }
+
+    fn add_pantry_item(&mut self, food_name: &str, weight_grams: f32) -> StorageResult<()> {
+        let food = self.food(food_name)?.clone();
+        self.pantry.push(PantryItem {
+            food,
+            weight_grams,
+            weight_grams_remaining: weight_grams,
+        });
+        Ok(())
+    }
+
+    fn recipes(&self) -> StorageResult<Vec<Recipe>> {
+        Ok(self.recipes.clone())
+    }
+
+    fn add_recipe(&mut self, name: &str, ingredients: &[(String, f32)]) -> StorageResult<()> {
+        if self.recipes.iter().any(|recipe| recipe.name == name) {
+            return Err(StorageError::Duplicate(name.to_string()));
+        }
+
+        let (foods, weights) = self.portions(ingredients)?;
+        self.recipes.push(Recipe {
+            name: name.to_string(),
+            foods,
+            weights,
+        });
+        Ok(())
+    }
+
+    fn entries(&self) -> StorageResult<Vec<Entry>> {
+        Ok(self.entries.clone())
+    }
+
+    fn add_entry(&mut self, timestamp: NaiveDateTime, foods: &[(String, f32)]) -> StorageResult<()> {
+        let (foods, weights) = self.portions(foods)?;
+        self.entries.push(Entry {
+            timestamp,
+            foods,
+            weights,
+        });
+        Ok(())
+    }
+}
+
+impl LookupCache for InMemoryRepository {
+    fn cached_lookup(&self, provider: &str, query: &str) -> StorageResult<Option<CachedLookup>> {
+        Ok(self.lookups.get(&(provider.to_string(), query.to_string())).cloned())
+    }
+
+    fn cache_lookup(
+        &mut self,
+        provider: &str,
+        query: &str,
+        foods: &[ProviderFood],
+        fetched_at: NaiveDateTime,
+    ) -> StorageResult<()> {
+        let cached = CachedLookup {
+            foods: foods.to_vec(),
+            fetched_at,
+        };
+        self.lookups.insert((provider.to_string(), query.to_string()), cached);
+        Ok(())
+    }
+}
+
+impl LookupQueue for InMemoryRepository {
+    fn pending_lookups(&self) -> StorageResult<Vec<PendingLookup>> {
+        Ok(self.pending.iter().map(|(lookup, _)| lookup.clone()).collect())
+    }
+
+    fn queue_lookup(&mut self, provider: &str, query: &str, queued_at: NaiveDateTime) -> StorageResult<i64> {
+        let query = normalize_query(query);
+        if let Some((waiting, _)) = self
+            .pending
+            .iter()
+            .find(|(lookup, _)| lookup.provider == provider && lookup.query == query)
+        {
+            return Ok(waiting.id);
+        }
+
+        self.next_lookup_id += 1;
+        let lookup = PendingLookup {
+            id: self.next_lookup_id,
+            provider: provider.to_string(),
+            query,
+            queued_at,
+            entry_timestamp: None,
+            attempts: 0,
+            last_error: None,
+            stalled: false,
+        };
+        self.pending.push((lookup, None));
+        Ok(self.next_lookup_id)
+    }
+
+    fn log_pending_entry(&mut self, lookup_id: i64, timestamp: NaiveDateTime) -> StorageResult<()> {
+        let index = self.entries.len();
+        if let Some((lookup, entry)) = self
+            .pending
+            .iter_mut()
+            .find(|(lookup, entry)| lookup.id == lookup_id && entry.is_none())
+        {
+            lookup.entry_timestamp = Some(timestamp);
+            *entry = Some(index);
+            self.entries.push(Entry {
+                timestamp,
+                foods: Vec::new(),
+                weights: Vec::new(),
+            });
+        }
+        Ok(())
+    }
+
+    fn resolve_lookup(&mut self, lookup_id: i64, foods: &[ProviderFood]) -> StorageResult<()> {
+        let position = match self.pending.iter().position(|(lookup, _)| lookup.id == lookup_id) {
+            Some(position) => position,
+            None => return Ok(()),
+        };
+        let (_, entry) = self.pending.remove(position);
+
+        for provided in foods {
+            let food = match self.food(&provided.name) {
+                Ok(food) => food.clone(),
+                Err(_) => {
+                    let food = Food::from(provided);
+                    self.foods.push(food.clone());
+                    food
+                }
+            };
+
+            if let Some(entry) = entry.and_then(|index| self.entries.get_mut(index)) {
+                entry.foods.push(food);
+                entry.weights.push(provided.serving_weight_grams);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn record_lookup_failure(&mut self, lookup_id: i64, error: &str, stalled: bool) -> StorageResult<()> {
+        if let Some((lookup, _)) = self.pending.iter_mut().find(|(lookup, _)| lookup.id == lookup_id) {
+            lookup.attempts += 1;
+            lookup.last_error = Some(error.to_string());
+            lookup.stalled = stalled;
+        }
+        Ok(())
+    }
+
+    // The placeholder entry stays, since removing it would shift the indexes
+    // other lookups hold; it has no foods, so it doesn't affect any totals.
+    fn discard_lookup(&mut self, lookup_id: i64) -> StorageResult<()> {
+        self.pending.retain(|(lookup, _)| lookup.id != lookup_id);
+        Ok(())
+    }
+}
+
+impl Storage for InMemoryRepository {
+    fn location(&self) -> String {
+        "in memory".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::measures::Measure;
+    use crate::provider::AmountBasis;
+
+    fn rice(serving_weight_grams: f32, nutrients: &[(NutrientKind, f32)]) -> ProviderFood {
+        ProviderFood {
+            name: "rice".to_string(),
+            serving_weight_grams,
+            nutrients: nutrients.iter().copied().collect(),
+            ..ProviderFood::default()
+        }
+    }
+
+    fn stored_rice(repository: &dyn Storage) -> Food {
+        repository.foods().unwrap().into_iter().find(|food| food.name == "rice").unwrap()
+    }
+
+    // Each way of settling a clash, in turn.
+    fn check_conflicts(repository: &mut dyn Storage) {
+        let original = rice(200.0, &[(NutrientKind::Protein, 4.0)]);
+        assert_eq!(repository.add_food(original.clone(), ConflictStrategy::Skip).unwrap(), AddOutcome::Added);
+        assert_eq!(repository.add_food(original.clone(), ConflictStrategy::Skip).unwrap(), AddOutcome::Skipped);
+
+        let outcomes = repository
+            .add_foods(vec![original.clone(), original.clone()], ConflictStrategy::KeepBoth)
+            .unwrap();
+        assert_eq!(
+            outcomes,
+            vec![
+                AddOutcome::Renamed("rice (2)".to_string()),
+                AddOutcome::Renamed("rice (3)".to_string()),
+            ]
+        );
+
+        // An entry eating 200 g of it sees each change below.
+        let timestamp = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        repository.log_entry(timestamp, &[("rice".to_string(), "200 g".to_string())]).unwrap();
+        let eaten = |repository: &dyn Storage, kind| repository.entries().unwrap()[0].totals()[&kind];
+
+        // Merging keeps stored values and fills gaps, rescaled from 50 g to 100 g.
+        let mut small = rice(50.0, &[(NutrientKind::Protein, 100.0), (NutrientKind::VitaminD, 1.0)]);
+        small.brand = Some("Mahatma".to_string());
+        assert_eq!(repository.add_food(small, ConflictStrategy::Merge).unwrap(), AddOutcome::Merged);
+        let merged = stored_rice(repository);
+        assert_eq!(merged.serving_grams, Some(200.0));
+        assert_eq!(merged.nutrient(NutrientKind::Protein), Some(2.0));
+        assert_eq!(merged.nutrient(NutrientKind::VitaminD), Some(2.0));
+        assert_eq!(merged.brand.as_deref(), Some("Mahatma"));
+        assert_eq!(eaten(repository, NutrientKind::Protein).amount, 4.0);
+        assert_eq!(eaten(repository, NutrientKind::VitaminD).amount, 4.0);
+
+        let mut aliased = merged;
+        aliased.aliases = vec!["arroz".to_string()];
+        repository.update_food("rice", &aliased).unwrap();
+
+        // Per 100 g with no serving of its own, so none is kept.
+        let replacement = ProviderFood {
+            basis: AmountBasis::Per100Grams,
+            ..rice(100.0, &[(NutrientKind::Protein, 3.0)])
+        };
+        assert_eq!(
+            repository.add_food(replacement, ConflictStrategy::Overwrite).unwrap(),
+            AddOutcome::Overwritten
+        );
+        let overwritten = stored_rice(repository);
+        assert_eq!(overwritten.serving_grams, None);
+        assert_eq!(overwritten.nutrient(NutrientKind::Protein), Some(3.0));
+        assert_eq!(overwritten.nutrient(NutrientKind::VitaminD), None);
+        assert_eq!(overwritten.brand, None);
+        assert_eq!(overwritten.aliases, vec!["arroz".to_string()]);
+        assert_eq!(eaten(repository, NutrientKind::Protein).amount, 6.0);
+        assert!(!eaten(repository, NutrientKind::VitaminD).is_complete());
+
+        assert_eq!(repository.foods().unwrap().len(), 3);
+    }
+
+    fn check_edit_and_delete(repository: &mut dyn Storage) {
+        let beans = ProviderFood {
+            name: "beans".to_string(),
+            serving_weight_grams: 100.0,
+            ..ProviderFood::default()
+        };
+        let cooked_rice = ProviderFood {
+            measures: vec![Measure::new("cup", 1.0, 160.0).unwrap()],
+            ..rice(200.0, &[(NutrientKind::Protein, 4.0)])
+        };
+        repository.add_foods(vec![cooked_rice, beans], ConflictStrategy::Skip).unwrap();
+        repository.add_pantry_item("rice", 500.0).unwrap();
+        repository
+            .add_recipe("rice and beans", &[("rice".to_string(), 100.0), ("beans".to_string(), 50.0)])
+            .unwrap();
+        let timestamp = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        repository.log_entry(timestamp, &[("rice".to_string(), "1/2 cup".to_string())]).unwrap();
+        assert_eq!(repository.entries().unwrap()[0].weights, vec![80.0]);
+
+        // 2 g of protein per 100 g, scaled to each portion.
+        let protein = |amounts: HashMap<NutrientKind, Option<f32>>| amounts[&NutrientKind::Protein].unwrap();
+        assert!((protein(repository.pantry().unwrap()[0].remaining_nutrients()) - 10.0).abs() < 0.001);
+        let eaten = repository.entries().unwrap()[0].totals()[&NutrientKind::Protein];
+        assert!((eaten.amount - 1.6).abs() < 0.001);
+
+        let mut edited = stored_rice(repository);
+        edited.name = "white rice".to_string();
+        edited.nutrients.insert(NutrientKind::Protein, None);
+        edited.nutrients.insert(NutrientKind::Fiber, Some(0.6));
+        edited.measures = vec![Measure { name: "bowl".to_string(), grams: 300.0 }];
+        repository.update_food("rice", &edited).unwrap();
+        let updated = repository.food_named("white rice").unwrap().unwrap();
+        assert_eq!(updated.measures, edited.measures);
+        assert_eq!(updated.nutrient(NutrientKind::Protein), None);
+        assert_eq!(updated.nutrient(NutrientKind::Fiber), Some(0.6));
+        assert_eq!(repository.entries().unwrap()[0].foods[0].name, "white rice");
+
+        // Logging a food twice in one entry, as a lookup of "1 egg and 2 eggs"
+        // does, mustn't lose any of its weight when it's reassigned.
+        let portions = [("white rice", "100"), ("white rice", "50"), ("beans", "20")];
+        let portions: Vec<(String, String)> =
+            portions.iter().map(|(name, amount)| (name.to_string(), amount.to_string())).collect();
+        repository.log_entry(timestamp, &portions).unwrap();
+
+        let used = repository.food_references("white rice").unwrap();
+        assert_eq!(used, FoodReferences { pantry: 1, recipes: 1, entries: 2 });
+        assert!(matches!(
+            repository.delete_food("white rice", ReferenceHandling::Refuse),
+            Err(StorageError::InUse { .. })
+        ));
+
+        // The recipe already has beans, so the two weights are combined.
+        repository
+            .delete_food("white rice", ReferenceHandling::ReassignTo("beans".to_string()))
+            .unwrap();
+        assert_eq!(repository.food_named("white rice").unwrap(), None);
+        let recipe = &repository.recipes().unwrap()[0];
+        assert_eq!(recipe.foods.len(), 1);
+        assert_eq!(recipe.weights, vec![150.0]);
+        let entry = &repository.entries().unwrap()[1];
+        assert!(entry.foods.iter().all(|food| food.name == "beans"));
+        assert_eq!(entry.weights.iter().sum::<f32>(), 170.0);
+        assert_eq!(repository.food_references("beans").unwrap(), used);
+
+        repository.delete_food("beans", ReferenceHandling::Cascade).unwrap();
+        assert!(repository.foods().unwrap().is_empty());
+        assert!(repository.pantry().unwrap().is_empty());
+        assert!(repository.recipes().unwrap()[0].foods.is_empty());
+    }
+
+    fn check_search(repository: &mut dyn Storage) {
+        let food = |name: &str, brand: Option<&str>| ProviderFood {
+            name: name.to_string(),
+            brand: brand.map(str::to_string),
+            serving_weight_grams: 100.0,
+            ..ProviderFood::default()
+        };
+        repository
+            .add_foods(
+                vec![food("Chicken breast", None), food("Chickpeas", None), food("Oat milk", Some("Oatly"))],
+                ConflictStrategy::Skip,
+            )
+            .unwrap();
+        let search = |repository: &dyn Storage, query: &str| -> Vec<String> {
+            repository.search_foods(query, 10).unwrap().into_iter().map(|food| food.name).collect()
+        };
+
+        // Both start with "chick"; the one-word name is the likelier pick.
+        assert_eq!(search(repository, "chick"), vec!["Chickpeas", "Chicken breast"]);
+        assert_eq!(search(repository, "chiken brest"), vec!["Chicken breast"]);
+        assert_eq!(search(repository, "oatly"), vec!["Oat milk"]);
+        assert!(search(repository, "garbanzo").is_empty());
+
+        // Edits reach the index: aliases are searchable and renames are found.
+        let mut chickpeas = repository.food_named("Chickpeas").unwrap().unwrap();
+        chickpeas.name = "Chick peas, canned".to_string();
+        chickpeas.aliases = vec!["garbanzo beans".to_string()];
+        repository.update_food("Chickpeas", &chickpeas).unwrap();
+        assert_eq!(search(repository, "garbanzo"), vec!["Chick peas, canned"]);
+        assert_eq!(search(repository, "canned"), vec!["Chick peas, canned"]);
+
+        repository.delete_food("Chick peas, canned", ReferenceHandling::Refuse).unwrap();
+        assert!(search(repository, "garbanzo").is_empty());
+    }
+
+    fn check_lookup_queue(repository: &mut dyn Storage) {
+        let queued_at = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        let id = repository.queue_lookup("Nutritionix", "2 Eggs", queued_at).unwrap();
+
+        // Submitting the same text again points at the lookup already waiting.
+        assert_eq!(repository.queue_lookup("Nutritionix", " 2 eggs  ", queued_at).unwrap(), id);
+        assert_ne!(repository.queue_lookup("Other", "2 eggs", queued_at).unwrap(), id);
+        repository.log_pending_entry(id, queued_at).unwrap();
+        repository.log_pending_entry(id, queued_at).unwrap();
+
+        let pending = repository.pending_lookups().unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].query, "2 eggs");
+        assert_eq!(repository.entries().unwrap().len(), 1);
+    }
+
+    // Holds both backends to the same behaviour. Output is only shown for a
+    // failing test, where it names the backend that failed.
+    fn for_each_backend(check: fn(&mut dyn Storage)) {
+        let backends: Vec<(&str, Box<dyn Storage>)> = vec![
+            ("SQLite", Box::new(SqliteRepository::open(":memory:").unwrap())),
+            ("in-memory", Box::new(InMemoryRepository::new())),
+        ];
+        for (backend, mut repository) in backends {
+            println!("checking the {} backend", backend);
+            check(repository.as_mut());
+        }
+    }
+
+    #[test]
+    fn queues_each_lookup_once() {
+        for_each_backend(check_lookup_queue);
+    }
+
+    #[test]
+    fn searches_foods() {
+        for_each_backend(check_search);
+    }
+
+    #[test]
+    fn edits_and_deletes_foods() {
+        for_each_backend(check_edit_and_delete);
+    }
+
+    #[test]
+    fn settles_name_conflicts() {
+        for_each_backend(check_conflicts);
+    }
+}
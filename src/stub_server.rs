@@ -0,0 +1,169 @@
+// A minimal HTTP server for tests that replays recorded Nutritionix
+// responses from fixtures/nutritionix, so lookups can be exercised end to end
+// without a network or real credentials.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub const APP_ID: &str = "stub-app-id";
+pub const APP_KEY: &str = "stub-app-key";
+
+const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/nutritionix");
+
+#[derive(Debug, Clone)]
+pub struct StubRoute {
+    pub method: &'static str,
+    pub path: &'static str,
+    // Must appear in the query string, e.g. "upc=0123".
+    pub query: Option<&'static str>,
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub fixture: &'static str,
+}
+
+impl StubRoute {
+    pub fn new(method: &'static str, path: &'static str, fixture: &'static str) -> StubRoute {
+        StubRoute {
+            method,
+            path,
+            query: None,
+            status: 200,
+            headers: Vec::new(),
+            fixture,
+        }
+    }
+
+    pub fn with_query(mut self, query: &'static str) -> StubRoute {
+        self.query = Some(query);
+        self
+    }
+
+    pub fn with_status(mut self, status: u16) -> StubRoute {
+        self.status = status;
+        self
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: &str) -> StubRoute {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub target: String,
+    pub body: String,
+}
+
+pub struct StubServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl StubServer {
+    // Serves `routes` in order of preference until the test process exits.
+    // Requests without the stub credentials get a 401 like the real API.
+    pub fn start(routes: Vec<StubRoute>) -> StubServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind stub server");
+        let addr = listener.local_addr().expect("stub server address");
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = serve(stream, &routes, &recorded);
+            }
+        });
+
+        StubServer { addr, requests }
+    }
+
+    // The three endpoints the app uses, answered from the recorded fixtures.
+    pub fn nutritionix() -> StubServer {
+        StubServer::start(vec![
+            StubRoute::new("POST", "/v2/natural/nutrients", "natural_nutrients.json"),
+            StubRoute::new("GET", "/v2/search/instant", "search_instant.json"),
+            StubRoute::new("GET", "/v2/search/item", "search_item.json").with_query("nix_item_id="),
+            StubRoute::new("GET", "/v2/search/item", "search_item.json").with_query("upc=0041196010022"),
+            StubRoute::new("GET", "/v2/search/item", "not_found.json").with_status(404),
+        ])
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn serve(stream: TcpStream, routes: &[StubRoute], recorded: &Mutex<Vec<RecordedRequest>>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    let mut app_id = None;
+    let mut app_key = None;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim().to_string();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.parse().unwrap_or(0),
+                "x-app-id" => app_id = Some(value),
+                "x-app-key" => app_key = Some(value),
+                _ => {}
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    recorded.lock().unwrap().push(RecordedRequest {
+        method: method.clone(),
+        target: target.clone(),
+        body: String::from_utf8_lossy(&body).into_owned(),
+    });
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let authorized = app_id.as_deref() == Some(APP_ID) && app_key.as_deref() == Some(APP_KEY);
+    let route = routes.iter().find(|route| {
+        route.method == method && route.path == path && route.query.is_none_or(|wanted| query.contains(wanted))
+    });
+
+    let (status, headers, fixture) = match route {
+        _ if !authorized => (401, Vec::new(), "unauthorized.json"),
+        Some(route) => (route.status, route.headers.clone(), route.fixture),
+        None => (404, Vec::new(), "not_found.json"),
+    };
+    let body = std::fs::read_to_string(format!("{}/{}", FIXTURE_DIR, fixture))?;
+
+    let mut response = format!(
+        "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        body.len()
+    );
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    response.push_str(&body);
+
+    let mut stream = stream;
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}